@@ -0,0 +1,356 @@
+//! Windows file attributes on Linux
+//!
+//! Native execution maps the DOS attributes robocopy works with onto unix files:
+//! READ_ONLY onto the write permission bits, HIDDEN onto a [`HiddenPolicy`]
+//! and the other attributes onto the extended attribute [`ATTRIBUTES_XATTR`].
+//! Attribute filters and post-copy actions both go through the same mapping.
+//!
+//! ARCHIVE is emulated: clearing it records the modification time and size of the file
+//! in [`ARCHIVE_XATTR`], and it counts as set again once either of them changed,
+//! which is whenever the file was written to. Without extended attributes it is always set.
+//!
+//! Filters read attributes through an [`AttributeProvider`]. Besides the mapping there are providers
+//! for the attributes Samba and ntfs-3g keep for Windows data, plain [`Heuristics`] and [`Permissions`].
+
+use std::{convert::TryInto, fmt, fs::{self, Metadata}, io, path::Path, time::UNIX_EPOCH};
+
+use crate::FileAttributes;
+
+/// Extended attribute holding the attribute letters robocopy uses, `ASCNET`, except `R`
+pub const ATTRIBUTES_XATTR: &str = "user.robocopyrs.attributes";
+
+/// Extended attribute holding the modification time and size of a file when its ARCHIVE was cleared
+pub const ARCHIVE_XATTR: &str = "user.robocopyrs.archive-cleared";
+
+const LETTERS: [char; 8] = ['R', 'A', 'S', 'H', 'C', 'N', 'E', 'T'];
+const READ_ONLY: usize = 0;
+const ARCHIVE: usize = 1;
+const HIDDEN: usize = 3;
+
+/// Where the HIDDEN attribute of a file comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HiddenPolicy {
+    /// Stored in the extended attribute with the other attributes
+    #[default]
+    ExtendedAttribute,
+    /// Files whose name starts with a dot are hidden, setting or clearing it does nothing
+    DotFiles,
+    /// No file is hidden
+    Ignore,
+}
+
+/// Maps attributes onto files
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AttributeMapping {
+    pub hidden: HiddenPolicy,
+}
+
+impl AttributeMapping {
+    pub fn new(hidden: HiddenPolicy) -> Self {
+        Self { hidden }
+    }
+
+    /// The attributes of a file.
+    /// File systems without extended attributes only have READ_ONLY and HIDDEN,
+    /// as do files whose extended attributes cannot be read.
+    pub fn read(&self, path: &Path, metadata: &Metadata) -> io::Result<FileAttributes> {
+        let mut flags = stored(path)?;
+        flags[READ_ONLY] = metadata.permissions().readonly();
+        flags[ARCHIVE] = archive_set(path, metadata)?;
+        flags[HIDDEN] = match self.hidden {
+            HiddenPolicy::ExtendedAttribute => flags[HIDDEN],
+            HiddenPolicy::DotFiles => path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.')),
+            HiddenPolicy::Ignore => false,
+        };
+
+        Ok(FileAttributes::_MULTIPLE(flags))
+    }
+
+    /// Sets the attributes of a file to exactly the given ones
+    pub fn write(&self, path: &Path, attributes: &FileAttributes) -> io::Result<()> {
+        let mut flags = attributes.flags();
+        let (read_only, archive) = (flags[READ_ONLY], flags[ARCHIVE]);
+        flags[READ_ONLY] = false;
+        flags[ARCHIVE] = false;
+        if self.hidden != HiddenPolicy::ExtendedAttribute {
+            flags[HIDDEN] = stored(path)?[HIDDEN];
+        }
+
+        // a read-only file may refuse changes to its extended attributes
+        if fs::metadata(path)?.permissions().readonly() {
+            set_read_only(path, false)?;
+        }
+        store(path, &flags)?;
+        set_archive(path, archive)?;
+        set_read_only(path, read_only)
+    }
+
+    /// Clears the ARCHIVE attribute of a file, as `/m` does after copying it
+    pub fn clear_archive(&self, path: &Path) -> io::Result<()> {
+        self.apply(path, &FileAttributes::none(), &FileAttributes::ARCHIVE)
+    }
+
+    /// Adds and then removes attributes of a file, like `/a+:` and `/a-:` do
+    pub fn apply(&self, path: &Path, add: &FileAttributes, remove: &FileAttributes) -> io::Result<()> {
+        let current = self.read(path, &fs::metadata(path)?)?.flags();
+        let (add, remove) = (add.flags(), remove.flags());
+
+        let mut flags = [false; 8];
+        for (index, flag) in flags.iter_mut().enumerate() {
+            *flag = (current[index] || add[index]) && !remove[index];
+        }
+
+        if flags == current {
+            return Ok(());
+        }
+        self.write(path, &FileAttributes::_MULTIPLE(flags))
+    }
+
+    /// Copies the attributes of one file to another
+    pub fn copy(&self, source: &Path, destination: &Path) -> io::Result<()> {
+        self.write(destination, &self.read(source, &fs::metadata(source)?)?)
+    }
+}
+
+/// Where the attributes filters are evaluated against come from
+pub trait AttributeProvider: fmt::Debug + Send + Sync {
+    /// The attributes of a file or directory
+    fn attributes(&self, path: &Path, metadata: &Metadata) -> io::Result<FileAttributes>;
+}
+
+impl AttributeProvider for AttributeMapping {
+    fn attributes(&self, path: &Path, metadata: &Metadata) -> io::Result<FileAttributes> {
+        self.read(path, metadata)
+    }
+}
+
+/// Attributes guessed from the name and permissions of a file:
+/// names starting with a dot are HIDDEN and files without any write bit are READ_ONLY
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Heuristics;
+
+impl AttributeProvider for Heuristics {
+    fn attributes(&self, path: &Path, metadata: &Metadata) -> io::Result<FileAttributes> {
+        let mut flags = [false; 8];
+        flags[READ_ONLY] = metadata.permissions().readonly();
+        flags[HIDDEN] = path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'));
+
+        Ok(FileAttributes::_MULTIPLE(flags))
+    }
+}
+
+/// Only READ_ONLY, from the write bits of a file. Plans read attributes through it
+/// when no filter needs the others, so extended attributes are not read for every file.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Permissions;
+
+impl AttributeProvider for Permissions {
+    fn attributes(&self, _path: &Path, metadata: &Metadata) -> io::Result<FileAttributes> {
+        let mut flags = [false; 8];
+        flags[READ_ONLY] = metadata.permissions().readonly();
+
+        Ok(FileAttributes::_MULTIPLE(flags))
+    }
+}
+
+/// Extended attribute Samba keeps the DOS attributes of a file in
+pub const SAMBA_XATTR: &str = "user.DOSATTRIB";
+
+/// Extended attribute ntfs-3g exposes the NTFS attributes of a file as
+pub const NTFS_3G_XATTR: &str = "system.ntfs_attrib";
+
+/// The Windows attribute bits of the attribute letters, in the order of `LETTERS`
+const DOS_BITS: [u32; 8] = [0x1, 0x20, 0x4, 0x2, 0x800, 0x2000, 0x4000, 0x100];
+
+/// The attributes of Windows attribute bits
+fn from_dos_bits(bits: u32) -> FileAttributes {
+    let mut flags = [false; 8];
+    for (flag, bit) in flags.iter_mut().zip(DOS_BITS.iter()) {
+        *flag = bits & bit != 0;
+    }
+
+    FileAttributes::_MULTIPLE(flags)
+}
+
+/// The attributes Samba stores in [`SAMBA_XATTR`] for files shared with Windows.
+/// Files Samba has not stored attributes for fall back to the [`Heuristics`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SambaDosAttrib;
+
+impl SambaDosAttrib {
+    /// Parses the value of the extended attribute, either the hexadecimal text of old Samba versions
+    /// or the NDR encoded `xattr_DosAttrib` structure. None if it holds no attributes.
+    pub fn parse(value: &[u8]) -> Option<u32> {
+        if let Some(hex) = value.strip_prefix(b"0x") {
+            let digits: String = hex.iter().take_while(|byte| byte.is_ascii_hexdigit()).map(|byte| *byte as char).collect();
+            return u32::from_str_radix(&digits, 16).ok();
+        }
+
+        let u32_at = |offset: usize| value.get(offset..offset + 4).map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()));
+        let version = u16::from_le_bytes(value.get(0..2)?.try_into().unwrap());
+        match version {
+            1 => u32_at(4),
+            2 => u32_at(8),
+            // the valid flags come first, the attributes are valid if their bit is set
+            3..=5 => u32_at(4).filter(|valid| valid & 1 != 0).and_then(|_| u32_at(8)),
+            _ => None,
+        }
+    }
+}
+
+impl AttributeProvider for SambaDosAttrib {
+    fn attributes(&self, path: &Path, metadata: &Metadata) -> io::Result<FileAttributes> {
+        match read_xattr(path, SAMBA_XATTR)?.as_deref().and_then(Self::parse) {
+            Some(bits) => Ok(from_dos_bits(bits)),
+            None => Heuristics.attributes(path, metadata),
+        }
+    }
+}
+
+/// The attributes ntfs-3g exposes in [`NTFS_3G_XATTR`] on mounted NTFS volumes.
+/// Other files fall back to the [`Heuristics`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Ntfs3gAttrib;
+
+impl AttributeProvider for Ntfs3gAttrib {
+    fn attributes(&self, path: &Path, metadata: &Metadata) -> io::Result<FileAttributes> {
+        match read_xattr(path, NTFS_3G_XATTR)? {
+            Some(value) if value.len() == 4 => Ok(from_dos_bits(u32::from_ne_bytes(value[..].try_into().unwrap()))),
+            _ => Heuristics.attributes(path, metadata),
+        }
+    }
+}
+
+/// The value of an extended attribute, none if the file or its file system does not have it
+/// or it cannot be read
+#[cfg(unix)]
+fn read_xattr(path: &Path, name: &str) -> io::Result<Option<Vec<u8>>> {
+    match xattr::get(path, name) {
+        Err(err) if unreadable(&err) => Ok(None),
+        result => result,
+    }
+}
+
+/// Whether an error reading an extended attribute means there is none to read,
+/// because the file system has none or the file denies reading them
+#[cfg(unix)]
+fn unreadable(err: &io::Error) -> bool {
+    matches!(err.kind(), io::ErrorKind::Unsupported | io::ErrorKind::PermissionDenied)
+}
+
+#[cfg(not(unix))]
+fn read_xattr(_path: &Path, _name: &str) -> io::Result<Option<Vec<u8>>> {
+    Ok(None)
+}
+
+/// Parses attribute letters, unknown letters are ignored
+fn parse(letters: &str) -> [bool; 8] {
+    let mut flags = [false; 8];
+    letters.chars().filter_map(|letter| LETTERS.iter().position(|known| known.eq_ignore_ascii_case(&letter)))
+        .for_each(|index| flags[index] = true);
+    flags
+}
+
+/// The attributes stored in the extended attribute of a file, none if it cannot be read
+#[cfg(unix)]
+fn stored(path: &Path) -> io::Result<[bool; 8]> {
+    match read_xattr(path, ATTRIBUTES_XATTR)? {
+        Some(value) => Ok(parse(&String::from_utf8_lossy(&value))),
+        None => Ok([false; 8]),
+    }
+}
+
+#[cfg(not(unix))]
+fn stored(_path: &Path) -> io::Result<[bool; 8]> {
+    Ok([false; 8])
+}
+
+/// Stores attributes in the extended attribute of a file, removing it when there are none
+#[cfg(unix)]
+fn store(path: &Path, flags: &[bool; 8]) -> io::Result<()> {
+    if flags.iter().any(|flag| *flag) {
+        let letters: String = LETTERS.iter().zip(flags.iter()).filter(|(_, set)| **set).map(|(letter, _)| *letter).collect();
+        return xattr::set(path, ATTRIBUTES_XATTR, letters.as_bytes());
+    }
+
+    match xattr::get(path, ATTRIBUTES_XATTR) {
+        Ok(Some(_)) => xattr::remove(path, ATTRIBUTES_XATTR),
+        Err(err) if err.kind() != io::ErrorKind::Unsupported => Err(err),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+fn store(_path: &Path, flags: &[bool; 8]) -> io::Result<()> {
+    match flags.iter().any(|flag| *flag) {
+        true => Err(io::Error::new(io::ErrorKind::Unsupported, "Attributes other than READ_ONLY are not supported on this platform.")),
+        false => Ok(()),
+    }
+}
+
+/// The modification time and size an archive record compares
+fn archive_stamp(metadata: &Metadata) -> String {
+    let modified = metadata.modified().ok().and_then(|modified| modified.duration_since(UNIX_EPOCH).ok()).unwrap_or_default();
+    format!("{}.{:09} {}", modified.as_secs(), modified.subsec_nanos(), metadata.len())
+}
+
+/// Whether the file changed since its ARCHIVE was cleared, or it never was
+/// or the record cannot be read
+#[cfg(unix)]
+fn archive_set(path: &Path, metadata: &Metadata) -> io::Result<bool> {
+    match read_xattr(path, ARCHIVE_XATTR)? {
+        Some(record) => Ok(record != archive_stamp(metadata).as_bytes()),
+        None => Ok(true),
+    }
+}
+
+#[cfg(not(unix))]
+fn archive_set(_path: &Path, _metadata: &Metadata) -> io::Result<bool> {
+    Ok(true)
+}
+
+/// Sets ARCHIVE by removing the record of the file or clears it by recording its current state.
+/// Without extended attributes there is no record and ARCHIVE stays set.
+#[cfg(unix)]
+fn set_archive(path: &Path, archive: bool) -> io::Result<()> {
+    if !archive {
+        return match xattr::set(path, ARCHIVE_XATTR, archive_stamp(&fs::metadata(path)?).as_bytes()) {
+            Err(err) if err.kind() == io::ErrorKind::Unsupported => Ok(()),
+            result => result,
+        };
+    }
+
+    match xattr::get(path, ARCHIVE_XATTR) {
+        Ok(Some(_)) => xattr::remove(path, ARCHIVE_XATTR),
+        Err(err) if err.kind() != io::ErrorKind::Unsupported => Err(err),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+fn set_archive(_path: &Path, archive: bool) -> io::Result<()> {
+    match archive {
+        true => Ok(()),
+        false => Err(io::Error::new(io::ErrorKind::Unsupported, "The archive attribute cannot be cleared on this platform.")),
+    }
+}
+
+/// Makes a file read-only by clearing all its write bits,
+/// or writable again by setting the write bit of its owner
+fn set_read_only(path: &Path, read_only: bool) -> io::Result<()> {
+    let mut permissions = fs::metadata(path)?.permissions();
+    if permissions.readonly() == read_only {
+        return Ok(());
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = permissions.mode();
+        permissions.set_mode(if read_only { mode & !0o222 } else { mode | 0o200 });
+    }
+    #[cfg(not(unix))]
+    permissions.set_readonly(read_only);
+
+    fs::set_permissions(path, permissions)
+}
@@ -0,0 +1,205 @@
+//! Robocopy's file classes
+//!
+//! Robocopy sorts each pair of source and destination entries into a [`FileClass`]
+//! by comparing their sizes, modification times and attributes. The class switches
+//! `/xx`, `/xl`, `/xc`, `/xn`, `/xo`, `/is`, `/it` and `/im` then decide which classes
+//! are copied. Dry-run planning and native execution both decide through a [`Classifier`].
+
+use std::{cmp::Ordering, time::{Duration, SystemTime}};
+
+use crate::{FilesystemOptions, MultipleVariant, RobocopyCommand};
+use crate::evaluate::{EntryMetadata, Rule};
+use crate::filter::{FileAndDirectoryExclusionFilter, FileExclusionFilter, FileExclusionFilterException, Filter};
+
+/// How far apart modification times can be and still count as the same
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimeTolerance {
+    /// `/fft`, times up to [`TimeTolerance::FAT_GRANULARITY`] apart are the same
+    pub fat_file_times: bool,
+    /// `/dst`, times [`TimeTolerance::DST_OFFSET`] apart are the same,
+    /// give or take the granularity
+    pub daylight_saving_time: bool,
+}
+
+impl TimeTolerance {
+    /// Resolution of FAT modification times
+    pub const FAT_GRANULARITY: Duration = Duration::from_secs(2);
+    /// The shift of clocks between standard and daylight saving time
+    pub const DST_OFFSET: Duration = Duration::from_secs(3600);
+
+    /// Compares the modification time of a source with that of its destination
+    pub fn compare(&self, source: SystemTime, destination: SystemTime) -> Ordering {
+        let (ordering, difference) = match source.duration_since(destination) {
+            Ok(difference) => (Ordering::Greater, difference),
+            Err(err) => (Ordering::Less, err.duration()),
+        };
+        let granularity = if self.fat_file_times { Self::FAT_GRANULARITY } else { Duration::ZERO };
+
+        let within = |expected: Duration| difference.abs_diff(expected) <= granularity;
+
+        if within(Duration::ZERO) || (self.daylight_saving_time && within(Self::DST_OFFSET)) {
+            Ordering::Equal
+        } else {
+            ordering
+        }
+    }
+}
+
+impl From<&FilesystemOptions> for TimeTolerance {
+    fn from(options: &FilesystemOptions) -> Self {
+        Self {
+            fat_file_times: options.contains(FilesystemOptions::ASSUME_FAT_FILE_TIMES),
+            daylight_saving_time: options.contains(FilesystemOptions::COMPENSATE_DST),
+        }
+    }
+}
+
+/// The classes robocopy sorts files and directories into
+/// by comparing the source with the destination
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum FileClass {
+    /// Only in the source, logged as "New File" or "New Dir" by robocopy
+    Lonely,
+    /// Only in the destination
+    Extra,
+    /// The source is newer than the destination
+    Newer,
+    /// The source is older than the destination
+    Older,
+    /// Same timestamp but a different size
+    Changed,
+    /// Same size and timestamp but different attributes
+    Tweaked,
+    /// Same size, timestamp and attributes but a different change time,
+    /// only metadata that has change times on both sides can be modified
+    Modified,
+    /// Identical or a directory that exists on both sides
+    Same,
+    /// A file on one side and a directory on the other
+    Mismatched,
+}
+
+impl FileClass {
+    /// Classifies a pair of source and destination entries comparing exact times,
+    /// there is no class when neither exists.
+    pub fn of(source: Option<&EntryMetadata>, destination: Option<&EntryMetadata>) -> Option<Self> {
+        Self::of_with(source, destination, TimeTolerance::default())
+    }
+
+    /// Classifies a pair of source and destination entries
+    /// with the tolerance of `/fft` and `/dst` for modification times.
+    pub fn of_with(source: Option<&EntryMetadata>, destination: Option<&EntryMetadata>, tolerance: TimeTolerance) -> Option<Self> {
+        Some(match (source, destination) {
+            (None, None) => return None,
+            (Some(_), None) => Self::Lonely,
+            (None, Some(_)) => Self::Extra,
+            (Some(source), Some(destination)) if source.is_dir != destination.is_dir => Self::Mismatched,
+            (Some(source), Some(_)) if source.is_dir => Self::Same,
+            (Some(source), Some(destination)) => {
+                let modified = tolerance.compare(source.modified, destination.modified);
+                if modified == Ordering::Greater {
+                    Self::Newer
+                } else if modified == Ordering::Less {
+                    Self::Older
+                } else if source.size != destination.size {
+                    Self::Changed
+                } else if source.attributes.flags() != destination.attributes.flags() {
+                    Self::Tweaked
+                } else if source.changed.is_some() && destination.changed.is_some() && source.changed != destination.changed {
+                    Self::Modified
+                } else {
+                    Self::Same
+                }
+            }
+        })
+    }
+}
+
+/// The tolerance and class switches a pair of entries is classified and decided with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Classifier {
+    pub tolerance: TimeTolerance,
+    /// `/xx`
+    pub exclude_extra: bool,
+    /// `/xl`
+    pub exclude_lonely: bool,
+    /// `/xc`
+    pub exclude_changed: bool,
+    /// `/xn`
+    pub exclude_newer: bool,
+    /// `/xo`
+    pub exclude_older: bool,
+    /// `/is`
+    pub include_same: bool,
+    /// `/it`
+    pub include_tweaked: bool,
+    /// `/im`
+    pub include_modified: bool,
+}
+
+impl Classifier {
+    /// Classifies a pair of source and destination entries with the tolerance of the classifier
+    pub fn classify(&self, source: Option<&EntryMetadata>, destination: Option<&EntryMetadata>) -> Option<FileClass> {
+        FileClass::of_with(source, destination, self.tolerance)
+    }
+
+    /// Whether robocopy acts on an entry of the class, which is copying it
+    /// or purging it for extras, or the switch that excludes it.
+    /// Newer, older and lonely files are copied unless excluded,
+    /// same, tweaked and modified files only when included.
+    /// Directories that exist on both sides are always walked into.
+    pub fn check(&self, class: FileClass, is_dir: bool) -> Result<(), Rule> {
+        match class {
+            FileClass::Lonely if self.exclude_lonely => Err(Rule::ExcludeLonely),
+            FileClass::Extra if self.exclude_extra => Err(Rule::ExcludeExtra),
+            FileClass::Newer if self.exclude_newer => Err(Rule::ExcludeNewer),
+            FileClass::Older if self.exclude_older => Err(Rule::ExcludeOlder),
+            FileClass::Changed if self.exclude_changed => Err(Rule::ExcludeChanged),
+            FileClass::Tweaked if !self.include_tweaked => Err(Rule::IncludeTweaked),
+            FileClass::Modified if !self.include_modified => Err(Rule::IncludeModified),
+            FileClass::Same if !self.include_same && !is_dir => Err(Rule::IncludeSame),
+            _ => Ok(()),
+        }
+    }
+
+    /// Classifies a pair of entries and checks the class, none if neither exists
+    pub fn decide(&self, source: Option<&EntryMetadata>, destination: Option<&EntryMetadata>) -> Option<Result<FileClass, Rule>> {
+        let is_dir = source.or(destination)?.is_dir;
+        self.classify(source, destination).map(|class| self.check(class, is_dir).map(|()| class))
+    }
+}
+
+impl From<&Filter> for Classifier {
+    /// The class switches of a filter, with exact times
+    fn from(filter: &Filter) -> Self {
+        let excluded = |variant| has_variant(filter.file_exclusion_filter.as_ref(), &variant);
+        let excluded_both = |variant| has_variant(filter.file_and_directory_exclusion_filter.as_ref(), &variant);
+        let included = |variant| has_variant(filter.file_exclusion_filter_exceptions.as_ref(), &variant);
+
+        Self {
+            tolerance: TimeTolerance::default(),
+            exclude_extra: excluded_both(FileAndDirectoryExclusionFilter::EXTRA),
+            exclude_lonely: excluded_both(FileAndDirectoryExclusionFilter::LONELY),
+            exclude_changed: excluded(FileExclusionFilter::CHANGED),
+            exclude_newer: excluded(FileExclusionFilter::NEWER),
+            exclude_older: excluded(FileExclusionFilter::OLDER),
+            include_same: included(FileExclusionFilterException::SAME),
+            include_tweaked: included(FileExclusionFilterException::TWEAKED),
+            include_modified: included(FileExclusionFilterException::MODIFIED),
+        }
+    }
+}
+
+impl From<&RobocopyCommand<'_>> for Classifier {
+    fn from(command: &RobocopyCommand<'_>) -> Self {
+        Self {
+            tolerance: command.filesystem_options.as_ref().map(TimeTolerance::from).unwrap_or_default(),
+            ..command.filter.as_ref().map(Self::from).unwrap_or_default()
+        }
+    }
+}
+
+/// Whether the filter is or contains the variant
+pub(crate) fn has_variant<T: MultipleVariant>(filter: Option<&T>, variant: &T) -> bool {
+    filter.is_some_and(|filter| filter.single_variants().iter().any(|single| std::mem::discriminant(single) == std::mem::discriminant(variant)))
+}
@@ -0,0 +1,328 @@
+//! Robocopy command lines
+//!
+//! Parses the arguments of robocopy, `<source> <destination> [<file>...] [<options>]`,
+//! into a [`RobocopyCommand`] like the `robocopyrs` binary does. Switches are case-insensitive.
+//! Besides the switches of robocopy the binary takes `--native` and `--external`
+//! to choose the engine and `--json` to print the summary as JSON.
+
+use std::{fmt, path::Path, str::FromStr};
+
+use crate::{CopyMode, DirectoryProperties, Engine, FileAttributes, FileProperties, FilesystemOptions, Move, PostCopyActions, RobocopyCommand};
+use crate::filter::{DirectoryExclusionFilter, FileAndDirectoryExclusionFilter, FileExclusionFilter, FileExclusionFilterException, Filter};
+use crate::logging::LoggingSettings;
+use crate::monitor::MonitorSettings;
+use crate::performance::{IoSettings, PerformanceChoice, PerformanceOptions, RetrySettings};
+
+/// Switches that only change what robocopy prints, they are accepted and ignored
+const OUTPUT_SWITCHES: [&str; 15] = ["np", "nfl", "ndl", "njh", "njs", "nc", "ns", "tee", "eta", "bytes", "ts", "fp", "v", "x", "unicode"];
+
+/// Switches that take a value after a colon, `/mt` and `/lfsm` also work without one
+const VALUE_SWITCHES: [&str; 27] = [
+    "lev", "copy", "dcopy", "xa", "ia", "max", "min", "maxage", "minage", "maxlad", "minlad", "mt", "ipg", "r", "w",
+    "iomaxsize", "iorate", "threshold", "lfsm", "log", "log+", "unilog", "unilog+", "a+", "a-", "mon", "mot",
+];
+
+/// A parsed command line
+#[derive(Debug, Clone)]
+pub struct Invocation<'a> {
+    pub command: RobocopyCommand<'a>,
+    /// `--native` or `--external`, robocopy on Windows and native execution elsewhere if neither is given
+    pub engine: Engine,
+    /// `--json`, print the summary as JSON rather than the output of robocopy
+    pub json: bool,
+}
+
+/// An argument that could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// Position of the argument counting from 1 like robocopy, one past the last for a missing argument
+    pub position: usize,
+    pub argument: String,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.argument.is_empty() {
+            f.write_str(self.reason)
+        } else {
+            write!(f, "Invalid Parameter #{} : \"{}\" {}", self.position, self.argument, self.reason)
+        }
+    }
+}
+
+/// The list that names without a switch are added to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum List {
+    Files,
+    ExcludedFiles,
+    ExcludedDirectories,
+}
+
+/// The options that are spread over several switches while parsing
+struct Parser<'a> {
+    command: RobocopyCommand<'a>,
+    engine: Engine,
+    json: bool,
+    list: List,
+    subdirectories: bool,
+    excluded_attributes: Option<FileAttributes>,
+    excluded_files: Vec<String>,
+    excluded_file_classes: [bool; 4],
+    excluded_directories: Vec<String>,
+    exclude_directory_junctions: bool,
+    excluded_classes: [bool; 3],
+    included_classes: [bool; 3],
+    performance: [bool; 3],
+    performance_choice: PerformanceChoice,
+    filesystem: [bool; 4],
+    add_attributes: Option<FileAttributes>,
+    remove_attributes: Option<FileAttributes>,
+}
+
+/// Parses a robocopy command line, without the name of the program
+pub fn parse<'a, S: AsRef<str>>(args: &'a [S]) -> Result<Invocation<'a>, ParseError> {
+    let mut parser = Parser {
+        command: RobocopyCommand::default(),
+        engine: if cfg!(windows) { Engine::External } else { Engine::Native },
+        json: false,
+        list: List::Files,
+        subdirectories: false,
+        excluded_attributes: None,
+        excluded_files: Vec::new(),
+        excluded_file_classes: [false; 4],
+        excluded_directories: Vec::new(),
+        exclude_directory_junctions: false,
+        excluded_classes: [false; 3],
+        included_classes: [false; 3],
+        performance: [false; 3],
+        performance_choice: PerformanceChoice::Default,
+        filesystem: [false; 4],
+        add_attributes: None,
+        remove_attributes: None,
+    };
+    let (mut source, mut destination) = (None, None);
+
+    for (index, arg) in args.iter().map(AsRef::as_ref).enumerate() {
+        let error = |reason| ParseError { position: index + 1, argument: String::from(arg), reason };
+
+        if let Some(option) = arg.strip_prefix("--") {
+            parser.option(option).map_err(error)?;
+        } else if source.is_none() {
+            source = Some(arg);
+        } else if destination.is_none() {
+            destination = Some(arg);
+        } else if let Some(switch) = arg.strip_prefix('/') {
+            let (name, value) = match switch.split_once(':') {
+                Some((name, value)) => (name, Some(value)),
+                None => (switch, None),
+            };
+            parser.switch(&name.to_ascii_lowercase(), value).map_err(error)?;
+        } else {
+            parser.name(arg);
+        }
+    }
+
+    let missing = |reason| ParseError { position: args.len() + 1, argument: String::new(), reason };
+    parser.command.source = Path::new(source.ok_or_else(|| missing("No Source Directory Specified."))?);
+    parser.command.destination = Path::new(destination.ok_or_else(|| missing("No Destination Directory Specified."))?);
+
+    Ok(parser.finish())
+}
+
+impl<'a> Parser<'a> {
+    /// The options of the binary, which robocopy does not have
+    fn option(&mut self, option: &str) -> Result<(), &'static str> {
+        match option {
+            "native" => self.engine = Engine::Native,
+            "external" => self.engine = Engine::External,
+            "json" => self.json = true,
+            _ => return Err("Unknown option."),
+        }
+        Ok(())
+    }
+
+    /// A file pattern, or a pattern of `/xf` or `/xd` when it follows them
+    fn name(&mut self, name: &'a str) {
+        match self.list {
+            List::Files => self.command.files.push(name),
+            List::ExcludedFiles => self.excluded_files.push(String::from(name)),
+            List::ExcludedDirectories => self.excluded_directories.push(String::from(name)),
+        }
+    }
+
+    fn switch(&mut self, name: &str, value: Option<&'a str>) -> Result<(), &'static str> {
+        if value.is_some() && !VALUE_SWITCHES.contains(&name) {
+            return Err("The switch takes no value.");
+        }
+        let required = || value.ok_or("The switch needs a value.");
+        self.list = List::Files;
+
+        match name {
+            "s" => self.subdirectories = true,
+            "e" => {
+                self.subdirectories = true;
+                self.command.empty_dir_copy = true;
+            },
+            "lev" => self.command.only_copy_top_n_levels = Some(number(required()?)?),
+            "z" => self.command.copy_mode = Some(CopyMode::RESTARTABLE_MODE),
+            "b" => self.command.copy_mode = Some(CopyMode::BACKUP_MODE),
+            "zb" => self.command.copy_mode = Some(CopyMode::RESTARTABLE_MODE_BACKUP_MODE_FALLBACK),
+            "j" => self.command.unbuffered = true,
+            "copy" => self.command.copy_file_properties = Some(required()?.parse()?),
+            "sec" => self.command.copy_file_properties = Some("DATS".parse()?),
+            "copyall" => self.command.copy_file_properties = Some(FileProperties::all()),
+            "nocopy" => self.command.copy_file_properties = Some(FileProperties::none()),
+            "dcopy" => self.command.copy_dir_properties = Some(required()?.parse()?),
+            "nodcopy" => self.command.copy_dir_properties = Some(DirectoryProperties::none()),
+            "timfix" => self.command.fix_file_times = true,
+            "purge" => self.command.remove_files_and_dirs_not_in_src = true,
+            "mir" => {
+                self.subdirectories = true;
+                self.command.empty_dir_copy = true;
+                self.command.remove_files_and_dirs_not_in_src = true;
+                self.command.overwrite_destination_dir_sec_settings_when_mirror = true;
+            },
+            "mov" => self.command.mv = Some(Move::FILES),
+            "move" => self.command.mv = Some(Move::FILES_AND_DIRS),
+            "a+" => self.add_attributes = Some(required()?.parse()?),
+            "a-" => self.remove_attributes = Some(required()?.parse()?),
+            "create" => self.command.structure_and_size_zero_files_only = true,
+            "fat" => self.filesystem[0] = true,
+            "fft" => self.filesystem[1] = true,
+            "256" => self.filesystem[2] = true,
+            "dst" => self.filesystem[3] = true,
+            "mon" => self.command.monitor.get_or_insert_with(MonitorSettings::default).changes = Some(number(required()?)?),
+            "mot" => self.command.monitor.get_or_insert_with(MonitorSettings::default).minutes = Some(number(required()?)?),
+
+            "a" => self.filter().archive_only = true,
+            "m" => self.filter().handle_archive_and_reset = true,
+            "ia" => self.filter().include_only_files_with_any_of_these_attribs = Some(required()?.parse()?),
+            "xa" => {
+                self.excluded_attributes = Some(required()?.parse()?);
+                self.filter();
+            },
+            "xf" => {
+                self.list = List::ExcludedFiles;
+                self.filter();
+            },
+            "xd" => {
+                self.list = List::ExcludedDirectories;
+                self.filter();
+            },
+            "xc" | "xo" | "xn" | "xjf" => {
+                self.excluded_file_classes[["xc", "xo", "xn", "xjf"].iter().position(|switch| *switch == name).unwrap()] = true;
+                self.filter();
+            },
+            "xjd" => {
+                self.exclude_directory_junctions = true;
+                self.filter();
+            },
+            "xx" | "xl" | "xj" => {
+                self.excluded_classes[["xx", "xl", "xj"].iter().position(|switch| *switch == name).unwrap()] = true;
+                self.filter();
+            },
+            "im" | "is" | "it" => {
+                self.included_classes[["im", "is", "it"].iter().position(|switch| *switch == name).unwrap()] = true;
+                self.filter();
+            },
+            "max" => self.filter().max_size = Some(required()?.parse()?),
+            "min" => self.filter().min_size = Some(required()?.parse()?),
+            "maxage" => self.filter().max_age = Some(required()?.parse()?),
+            "minage" => self.filter().min_age = Some(required()?.parse()?),
+            "maxlad" => self.filter().max_last_access_date = Some(required()?.parse()?),
+            "minlad" => self.filter().min_last_access_date = Some(required()?.parse()?),
+
+            "mt" => {
+                let threads = value.map(number).transpose()?.unwrap_or(PerformanceChoice::DEFAULT_THREADS);
+                if !(1..=PerformanceChoice::MAX_THREADS).contains(&threads) {
+                    return Err("The number of threads must be between 1 and 128.");
+                }
+                self.performance_choice = PerformanceChoice::Threads(threads);
+            },
+            "ipg" => self.performance_choice = PerformanceChoice::InterPacketGap(number(required()?)?),
+            "nooffload" => self.performance[0] = true,
+            "compress" => self.performance[1] = true,
+            "sl" => self.performance[2] = true,
+            "r" => self.command.retry_settings.get_or_insert_with(RetrySettings::default).specify_retries_failed_copies = Some(number(required()?)?),
+            "w" => self.command.retry_settings.get_or_insert_with(RetrySettings::default).specify_wait_between_retries = Some(number(required()?)?),
+            "reg" => self.command.retry_settings.get_or_insert_with(RetrySettings::default).save_specifications = true,
+            "tbd" => self.command.retry_settings.get_or_insert_with(RetrySettings::default).await_share_names_def = true,
+            "iomaxsize" => self.command.io_settings.get_or_insert_with(IoSettings::default).max_io_size = Some(required()?.parse()?),
+            "iorate" => self.command.io_settings.get_or_insert_with(IoSettings::default).io_rate = Some(required()?.parse()?),
+            "threshold" => self.command.io_settings.get_or_insert_with(IoSettings::default).throttle_threshold = Some(required()?.parse()?),
+            "lfsm" => {
+                let settings = self.command.io_settings.get_or_insert_with(IoSettings::default);
+                settings.low_free_space_mode = true;
+                settings.low_free_space_floor = value.map(str::parse).transpose()?;
+            },
+
+            "log" | "log+" | "unilog" | "unilog+" => self.command.logging = Some(LoggingSettings {
+                log: Path::new(required()?),
+                unicode: name.starts_with("uni"),
+                append: name.ends_with('+'),
+            }),
+            name if OUTPUT_SWITCHES.contains(&name) => (),
+            _ => return Err("Unknown or unsupported switch."),
+        }
+
+        Ok(())
+    }
+
+    /// The filter of the command, which has one as soon as a filter switch is given
+    fn filter(&mut self) -> &mut Filter {
+        self.command.filter.get_or_insert_with(Filter::default)
+    }
+
+    /// The command with the options that are spread over several switches
+    fn finish(mut self) -> Invocation<'a> {
+        // without /s, /e or /mir robocopy only copies the source directory itself
+        if !self.subdirectories && self.command.only_copy_top_n_levels.is_none() {
+            self.command.only_copy_top_n_levels = Some(1);
+        }
+
+        if let Some(filter) = &mut self.command.filter {
+            if self.excluded_attributes.is_some() || !self.excluded_files.is_empty() || self.excluded_file_classes.contains(&true) {
+                filter.file_exclusion_filter = Some(FileExclusionFilter::_MULTIPLE(self.excluded_attributes, self.excluded_files, self.excluded_file_classes));
+            }
+            filter.directory_exclusion_filter = match (self.excluded_directories.is_empty(), self.exclude_directory_junctions) {
+                (true, false) => None,
+                (true, true) => Some(DirectoryExclusionFilter::JUNCTION_POINTS),
+                (false, false) => Some(DirectoryExclusionFilter::PathOrName(self.excluded_directories)),
+                (false, true) => Some(DirectoryExclusionFilter::_BOTH(self.excluded_directories)),
+            };
+            if self.excluded_classes.contains(&true) {
+                filter.file_and_directory_exclusion_filter = Some(FileAndDirectoryExclusionFilter::_MULTIPLE(self.excluded_classes));
+            }
+            if self.included_classes.contains(&true) {
+                filter.file_exclusion_filter_exceptions = Some(FileExclusionFilterException::_MULTIPLE(self.included_classes));
+            }
+        }
+
+        if self.performance.contains(&true) {
+            self.command.performance_options = Some(PerformanceOptions::_MULTIPLE(self.performance, self.performance_choice));
+        } else if self.performance_choice != PerformanceChoice::Default {
+            self.command.performance_options = Some(PerformanceOptions::PerformanceChoiceOnly(self.performance_choice));
+        }
+        if self.filesystem.contains(&true) {
+            self.command.filesystem_options = Some(FilesystemOptions::_MULTIPLE(self.filesystem));
+        }
+        self.command.post_copy_actions = match (self.add_attributes, self.remove_attributes) {
+            (None, None) => None,
+            (Some(add), None) => Some(PostCopyActions::AddAttribsToFiles(add)),
+            (None, Some(remove)) => Some(PostCopyActions::RmvAttribsFromFiles(remove)),
+            (Some(add), Some(remove)) => Some(PostCopyActions::_MULTIPLE(add, remove)),
+        };
+
+        Invocation {
+            command: self.command,
+            engine: self.engine,
+            json: self.json,
+        }
+    }
+}
+
+/// The number of a switch such as `/r:3`
+fn number<T: FromStr>(value: &str) -> Result<T, &'static str> {
+    value.parse().map_err(|_| "The value must be a number.")
+}
@@ -0,0 +1,427 @@
+//! In-process evaluation of the robocopy selection rules
+//!
+//! Answers whether robocopy would copy a file and, if not, which rule excluded it,
+//! without running robocopy. The metadata is passed in, so the rules can be
+//! evaluated against fixtures as well as against files on disk.
+
+use std::{fmt, fs::Metadata, path::{Component, Path, PathBuf}, time::{SystemTime, UNIX_EPOCH}};
+
+use crate::{FileAttributes, RobocopyCommand};
+use crate::classify::{has_variant, Classifier};
+use crate::filter::{DirectoryExclusionFilter, FileAndDirectoryExclusionFilter, FileExclusionFilter, Filter};
+
+pub use crate::classify::{FileClass, TimeTolerance};
+use crate::wildcard::WildcardSet;
+
+/// The metadata of a file or directory that the rules look at
+#[derive(Debug, Clone, Copy)]
+pub struct EntryMetadata {
+    pub is_dir: bool,
+    pub size: u64,
+    pub modified: SystemTime,
+    pub accessed: SystemTime,
+    /// Time of the last change to the file or its metadata.
+    /// None for entries read from disk, a copy cannot be given the change time of its source,
+    /// so comparing it would make every copied file `Modified`.
+    pub changed: Option<SystemTime>,
+    pub attributes: FileAttributes,
+    /// What the entry points to if it is a symbolic link
+    pub link: Option<Link>,
+    /// A FIFO, socket or device, which is never opened
+    pub special: bool,
+}
+
+/// The target of a symbolic link
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Link {
+    File,
+    Directory,
+    /// The target does not exist
+    Broken,
+}
+
+impl EntryMetadata {
+    /// Metadata of a file without attributes that was last accessed when it was modified
+    pub fn file(size: u64, modified: SystemTime) -> Self {
+        Self {
+            is_dir: false,
+            size,
+            modified,
+            accessed: modified,
+            changed: None,
+            attributes: FileAttributes::none(),
+            link: None,
+            special: false,
+        }
+    }
+
+    /// Metadata of a directory without attributes
+    pub fn directory(modified: SystemTime) -> Self {
+        Self {
+            is_dir: true,
+            ..Self::file(0, modified)
+        }
+    }
+}
+
+impl From<&Metadata> for EntryMetadata {
+    fn from(metadata: &Metadata) -> Self {
+        let mut attributes = [false; 8];
+        attributes[0] = metadata.permissions().readonly();
+
+        Self {
+            is_dir: metadata.is_dir(),
+            size: if metadata.is_dir() { 0 } else { metadata.len() },
+            modified: metadata.modified().unwrap_or(UNIX_EPOCH),
+            accessed: metadata.accessed().unwrap_or(UNIX_EPOCH),
+            changed: None,
+            attributes: FileAttributes::_MULTIPLE(attributes),
+            link: None,
+            special: !metadata.is_dir() && !metadata.is_file() && !metadata.file_type().is_symlink(),
+        }
+    }
+}
+
+/// The rule that excluded a file or directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rule {
+    /// Not matched by any of the `files` patterns
+    Files,
+    /// Matched the `/xf` pattern
+    ExcludeFiles(String),
+    /// Matched the `/xd` pattern, for files this can be a parent directory
+    ExcludeDirectories(String),
+    /// Deeper than `/lev` allows
+    Levels,
+    /// Has an attribute excluded by `/xa`
+    ExcludeAttributes,
+    /// Has none of the attributes of `/ia`
+    IncludeAttributes,
+    /// Does not have the archive attribute and `/a` is set
+    Archive,
+    /// Does not have the archive attribute and `/m` is set
+    ArchiveAndReset,
+    /// Larger than `/max`
+    MaxSize,
+    /// Smaller than `/min`
+    MinSize,
+    /// Modified before `/maxage`
+    MaxAge,
+    /// Modified after `/minage`
+    MinAge,
+    /// Accessed before `/maxlad`
+    MaxLastAccessDate,
+    /// Accessed after `/minlad`
+    MinLastAccessDate,
+    /// Older than the destination and `/xo` is set
+    ExcludeOlder,
+    /// Newer than the destination and `/xn` is set
+    ExcludeNewer,
+    /// Changed and `/xc` is set
+    ExcludeChanged,
+    /// Extra and `/xx` is set
+    ExcludeExtra,
+    /// Lonely and `/xl` is set
+    ExcludeLonely,
+    /// Same and `/is` is not set
+    IncludeSame,
+    /// Tweaked and `/it` is not set
+    IncludeTweaked,
+    /// Modified and `/im` is not set
+    IncludeModified,
+    /// A symbolic link and `/xj` is set
+    ExcludeJunctions,
+    /// A symbolic link to a directory and `/xjd` is set
+    ExcludeDirectoryJunctions,
+    /// A symbolic link to a file and `/xjf` is set
+    ExcludeFileJunctions,
+    /// A symbolic link to nothing that would be followed
+    BrokenLink,
+    /// A symbolic link to one of its own parent directories
+    LinkCycle,
+    /// A FIFO, socket or device on either side, which cannot be copied
+    SpecialFile,
+}
+
+impl Rule {
+    /// The robocopy switch of the rule
+    pub fn switch(&self) -> &'static str {
+        match self {
+            Self::Files => "files",
+            Self::ExcludeFiles(_) => "/xf",
+            Self::ExcludeDirectories(_) => "/xd",
+            Self::Levels => "/lev",
+            Self::ExcludeAttributes => "/xa",
+            Self::IncludeAttributes => "/ia",
+            Self::Archive => "/a",
+            Self::ArchiveAndReset => "/m",
+            Self::MaxSize => "/max",
+            Self::MinSize => "/min",
+            Self::MaxAge => "/maxage",
+            Self::MinAge => "/minage",
+            Self::MaxLastAccessDate => "/maxlad",
+            Self::MinLastAccessDate => "/minlad",
+            Self::ExcludeOlder => "/xo",
+            Self::ExcludeNewer => "/xn",
+            Self::ExcludeChanged => "/xc",
+            Self::ExcludeExtra => "/xx",
+            Self::ExcludeLonely => "/xl",
+            Self::IncludeSame => "/is",
+            Self::IncludeTweaked => "/it",
+            Self::IncludeModified => "/im",
+            Self::ExcludeJunctions => "/xj",
+            Self::ExcludeDirectoryJunctions => "/xjd",
+            Self::ExcludeFileJunctions => "/xjf",
+            Self::BrokenLink => "broken link",
+            Self::LinkCycle => "link cycle",
+            Self::SpecialFile => "special file",
+        }
+    }
+
+    /// Whether the rule skipped a symbolic link
+    pub fn is_link_rule(&self) -> bool {
+        matches!(self, Self::ExcludeJunctions | Self::ExcludeDirectoryJunctions | Self::ExcludeFileJunctions | Self::BrokenLink | Self::LinkCycle)
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ExcludeFiles(pattern) | Self::ExcludeDirectories(pattern) => write!(f, "{} {}", self.switch(), pattern),
+            rule => write!(f, "{}", rule.switch()),
+        }
+    }
+}
+
+/// Whether a file or directory is included and why
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decision {
+    Include(FileClass),
+    Exclude(Rule),
+}
+
+impl Decision {
+    pub fn is_included(&self) -> bool {
+        matches!(self, Self::Include(_))
+    }
+}
+
+/// Evaluates the filters of a command against files and directories
+#[derive(Debug, Clone)]
+pub struct Evaluator {
+    filter: Filter,
+    files: WildcardSet,
+    excluded_files: WildcardSet,
+    excluded_dirs: WildcardSet,
+    only_copy_top_n_levels: Option<usize>,
+    source_root: PathBuf,
+    now: SystemTime,
+    classifier: Classifier,
+    copy_links: bool,
+}
+
+impl Evaluator {
+    pub fn new<S: AsRef<str>>(filter: Option<&Filter>, files: &[S], only_copy_top_n_levels: Option<usize>) -> Self {
+        let filter = filter.cloned().unwrap_or_default();
+
+        Self {
+            files: WildcardSet::files(files),
+            excluded_files: filter.file_exclusion_filter.as_ref().map(FileExclusionFilter::wildcards).unwrap_or_default(),
+            excluded_dirs: filter.directory_exclusion_filter.as_ref().map(|filter| filter.wildcards()).unwrap_or_default(),
+            classifier: Classifier::from(&filter),
+            filter,
+            only_copy_top_n_levels,
+            source_root: PathBuf::new(),
+            now: SystemTime::now(),
+            copy_links: false,
+        }
+    }
+
+    /// Sets the root that relative paths are joined to
+    /// before they are matched against full path patterns.
+    pub fn with_source_root(mut self, source_root: &Path) -> Self {
+        self.source_root = source_root.to_path_buf();
+        self
+    }
+
+    /// Sets the time that ages in days are counted back from, the default is now.
+    pub fn with_now(mut self, now: SystemTime) -> Self {
+        self.now = now;
+        self
+    }
+
+    /// Sets the tolerance modification times are compared with, the default compares exact times.
+    pub fn with_time_tolerance(mut self, tolerance: TimeTolerance) -> Self {
+        self.classifier.tolerance = tolerance;
+        self
+    }
+
+    /// Sets whether symbolic links are copied as links (`/sl`), the default follows them.
+    pub fn with_copy_links(mut self, copy_links: bool) -> Self {
+        self.copy_links = copy_links;
+        self
+    }
+
+    /// Classifies a pair of source and destination entries with the tolerance of the evaluator
+    pub fn classify(&self, source: Option<&EntryMetadata>, destination: Option<&EntryMetadata>) -> Option<FileClass> {
+        self.classifier.classify(source, destination)
+    }
+
+    /// Evaluates a directory given by its path relative to the source root.
+    pub fn evaluate_directory(&self, path: &Path, source: Option<&EntryMetadata>, destination: Option<&EntryMetadata>) -> Decision {
+        if let Err(rule) = self.check_directories(path, true).and_then(|_| self.check_link(source.or(destination))) {
+            return Decision::Exclude(rule);
+        }
+
+        match self.classifier.decide(source, destination) {
+            Some(Ok(class)) => Decision::Include(class),
+            Some(Err(rule)) => Decision::Exclude(rule),
+            None => Decision::Exclude(Rule::Files),
+        }
+    }
+
+    /// Evaluates a file given by its path relative to the source root.
+    pub fn evaluate_file(&self, path: &Path, source: Option<&EntryMetadata>, destination: Option<&EntryMetadata>) -> Decision {
+        if let Err(rule) = self.check_directories(path, false).and_then(|_| self.check_names(path)).and_then(|_| self.check_link(source.or(destination))) {
+            return Decision::Exclude(rule);
+        }
+        if source.is_some() && source.into_iter().chain(destination).any(|metadata| metadata.special) {
+            return Decision::Exclude(Rule::SpecialFile);
+        }
+        if let Some(source) = source {
+            if let Err(rule) = self.check_source(source) {
+                return Decision::Exclude(rule);
+            }
+        }
+
+        match self.classifier.decide(source, destination) {
+            Some(Ok(class)) => Decision::Include(class),
+            Some(Err(rule)) => Decision::Exclude(rule),
+            None => Decision::Exclude(Rule::Files),
+        }
+    }
+
+    /// Checks the rules that only depend on the path, `/lev` and `/xd` and for files also
+    /// the `files` patterns and `/xf`, so changes to paths that are excluded anyway can be ignored.
+    pub fn check_path(&self, path: &Path, is_dir: bool) -> Result<(), Rule> {
+        self.check_directories(path, is_dir)?;
+        match is_dir {
+            true => Ok(()),
+            false => self.check_names(path),
+        }
+    }
+
+    /// Checks `/lev` and `/xd` for the parent directories of the path
+    /// and the path itself if it is a directory
+    fn check_directories(&self, path: &Path, is_dir: bool) -> Result<(), Rule> {
+        let components: Vec<Component> = path.components().filter(|component| matches!(component, Component::Normal(_))).collect();
+        let level = components.len() + if is_dir { 1 } else { 0 };
+
+        if let Some(levels) = self.only_copy_top_n_levels {
+            if level > levels {
+                return Err(Rule::Levels);
+            }
+        }
+
+        let dirs = if is_dir { components.len() } else { components.len().saturating_sub(1) };
+        let mut dir = self.source_root.clone();
+        for component in components.iter().take(dirs) {
+            dir.push(component);
+            if let Some(pattern) = self.excluded_dirs.match_path(&dir) {
+                return Err(Rule::ExcludeDirectories(String::from(pattern.as_str())));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks the `files` patterns and `/xf`
+    fn check_names(&self, path: &Path) -> Result<(), Rule> {
+        let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+        if !self.files.matches_name(&name) {
+            return Err(Rule::Files);
+        }
+
+        match self.excluded_files.match_path(&self.source_root.join(path)) {
+            Some(pattern) => Err(Rule::ExcludeFiles(String::from(pattern.as_str()))),
+            None => Ok(())
+        }
+    }
+
+    /// Checks `/xj`, `/xjd` and `/xjf` for symbolic links,
+    /// and that links that would be followed lead somewhere
+    fn check_link(&self, metadata: Option<&EntryMetadata>) -> Result<(), Rule> {
+        let filter = &self.filter;
+        let link = match metadata.and_then(|metadata| metadata.link) {
+            Some(link) => link,
+            None => return Ok(()),
+        };
+
+        if has_variant(filter.file_and_directory_exclusion_filter.as_ref(), &FileAndDirectoryExclusionFilter::JUNCTION_POINTS) {
+            return Err(Rule::ExcludeJunctions);
+        }
+        match link {
+            Link::Directory if has_variant(filter.directory_exclusion_filter.as_ref(), &DirectoryExclusionFilter::JUNCTION_POINTS) => Err(Rule::ExcludeDirectoryJunctions),
+            Link::File | Link::Broken if has_variant(filter.file_exclusion_filter.as_ref(), &FileExclusionFilter::JUNCTION_POINTS) => Err(Rule::ExcludeFileJunctions),
+            Link::Broken if !self.copy_links => Err(Rule::BrokenLink),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the attribute, size and age rules against the source file
+    fn check_source(&self, source: &EntryMetadata) -> Result<(), Rule> {
+        let filter = &self.filter;
+
+        if (filter.archive_only || filter.handle_archive_and_reset) && !source.attributes.contains_any(&FileAttributes::ARCHIVE) {
+            return Err(if filter.handle_archive_and_reset { Rule::ArchiveAndReset } else { Rule::Archive });
+        }
+        if let Some(attribs) = filter.file_exclusion_filter.as_ref().and_then(Self::excluded_attributes) {
+            if source.attributes.contains_any(&attribs) {
+                return Err(Rule::ExcludeAttributes);
+            }
+        }
+        if let Some(attribs) = filter.include_only_files_with_any_of_these_attribs {
+            if !source.attributes.contains_any(&attribs) {
+                return Err(Rule::IncludeAttributes);
+            }
+        }
+
+        if filter.max_size.is_some_and(|max| source.size > max.bytes()) {
+            return Err(Rule::MaxSize);
+        }
+        if filter.min_size.is_some_and(|min| source.size < min.bytes()) {
+            return Err(Rule::MinSize);
+        }
+
+        if filter.max_age.is_some_and(|age| source.modified < age.cutoff(self.now)) {
+            return Err(Rule::MaxAge);
+        }
+        if filter.min_age.is_some_and(|age| source.modified > age.cutoff(self.now)) {
+            return Err(Rule::MinAge);
+        }
+        if filter.max_last_access_date.is_some_and(|age| source.accessed < age.cutoff(self.now)) {
+            return Err(Rule::MaxLastAccessDate);
+        }
+        if filter.min_last_access_date.is_some_and(|age| source.accessed > age.cutoff(self.now)) {
+            return Err(Rule::MinLastAccessDate);
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn excluded_attributes(filter: &FileExclusionFilter) -> Option<FileAttributes> {
+        match filter {
+            FileExclusionFilter::Attributes(attribs) | FileExclusionFilter::_MULTIPLE(Some(attribs), _, _) => Some(*attribs),
+            _ => None,
+        }
+    }
+}
+
+impl From<&RobocopyCommand<'_>> for Evaluator {
+    fn from(command: &RobocopyCommand<'_>) -> Self {
+        Self::new(command.filter.as_ref(), &command.files, command.only_copy_top_n_levels)
+            .with_source_root(command.source)
+            .with_time_tolerance(command.filesystem_options.as_ref().map(TimeTolerance::from).unwrap_or_default())
+            .with_copy_links(command.performance_options.is_some_and(|options| options.copies_links()))
+    }
+}
@@ -2,8 +2,10 @@
 //! 
 //! All filters and exceptions are handled by the Filter struct
 
-use std::{convert::TryInto, ffi::OsString, ops::Add};
+use std::{convert::{TryFrom, TryInto}, ffi::OsString, ops::Add, str::FromStr, time::{Duration, SystemTime, UNIX_EPOCH}};
 use crate::FileAttributes;
+use crate::size::ByteSize;
+use crate::wildcard::WildcardSet;
 use crate::MultipleVariant;
 
 /// Filters out files that match the variant
@@ -102,6 +104,14 @@ impl From<FileExclusionFilter> for Vec<OsString> {
 }
 
 impl FileExclusionFilter {
+    /// The `/xf` patterns of the filter
+    pub fn wildcards(&self) -> WildcardSet {
+        match self {
+            Self::PathOrName(path_or_name) | Self::_MULTIPLE(_, path_or_name, _) => WildcardSet::new(path_or_name),
+            _ => WildcardSet::default()
+        }
+    }
+
     const VARIANTS: [Self; 4] = [
         Self::CHANGED,
         Self::OLDER,
@@ -172,6 +182,16 @@ impl From<DirectoryExclusionFilter> for Vec<OsString> {
     }
 }
 
+impl DirectoryExclusionFilter {
+    /// The `/xd` patterns of the filter
+    pub fn wildcards(&self) -> WildcardSet {
+        match self {
+            Self::PathOrName(path_or_name) | Self::_BOTH(path_or_name) => WildcardSet::new(path_or_name),
+            Self::JUNCTION_POINTS => WildcardSet::default()
+        }
+    }
+}
+
 impl MultipleVariant for DirectoryExclusionFilter {
     fn single_variants(&self) -> Vec<Self> {
         match self {
@@ -237,7 +257,7 @@ impl MultipleVariant for FileAndDirectoryExclusionFilter {
     fn single_variants(&self) -> Vec<Self> {
         match self {
             Self::_MULTIPLE(filters) => {
-                Self::VARIANTS.iter().zip(filters.iter()).filter(|(_, exists)| **exists).into_iter().unzip::<&Self, &bool, Vec<Self>, Vec<bool>>().0
+                Self::VARIANTS.iter().zip(filters.iter()).filter(|(_, exists)| **exists).unzip::<&Self, &bool, Vec<Self>, Vec<bool>>().0
             },
             attrib => vec![*attrib],
         }
@@ -314,7 +334,7 @@ impl MultipleVariant for FileExclusionFilterException {
     fn single_variants(&self) -> Vec<Self> {
         match self {
             Self::_MULTIPLE(filters) => {
-                Self::VARIANTS.iter().zip(filters.iter()).filter(|(_, exists)| **exists).into_iter().unzip::<&Self, &bool, Vec<Self>, Vec<bool>>().0
+                Self::VARIANTS.iter().zip(filters.iter()).filter(|(_, exists)| **exists).unzip::<&Self, &bool, Vec<Self>, Vec<bool>>().0
             },
             attrib => vec![*attrib],
         }
@@ -341,9 +361,168 @@ impl FileExclusionFilterException {
     }
 }
 
+/// An age or date limit used by the `/maxage`, `/minage`, `/maxlad` and `/minlad` options
+/// 
+/// Robocopy treats values below 1900 as a number of days and 
+/// anything else as a date in the form `YYYYMMDD`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AgeLimit {
+    Days(u32),
+    Date { year: u16, month: u8, day: u8 },
+}
+
+impl AgeLimit {
+    const SECONDS_PER_DAY: u64 = 86_400;
+
+    /// Creates a limit of `days` days. 
+    /// Robocopy reads values of 1900 or more as a year, so they are rejected.
+    pub fn days(days: u32) -> Result<Self, &'static str> {
+        if days < 1900 {
+            Ok(Self::Days(days))
+        } else {
+            Err("Age limits of 1900 days or more are read as dates by robocopy.")
+        }
+    }
+
+    /// Creates a limit on a calendar date.
+    pub fn date(year: u16, month: u8, day: u8) -> Result<Self, &'static str> {
+        if !(1900..=9999).contains(&year) {
+            return Err("The year of an age limit must be between 1900 and 9999.");
+        }
+        if !(1..=12).contains(&month) {
+            return Err("The month of an age limit must be between 1 and 12.");
+        }
+        if day == 0 || day > Self::days_in_month(year, month) {
+            return Err("The day of an age limit does not exist in that month.");
+        }
+
+        Ok(Self::Date { year, month, day })
+    }
+
+    /// Checks a limit that was constructed without using `days` or `date`.
+    pub fn validate(&self) -> Result<(), &'static str> {
+        match *self {
+            Self::Days(days) => Self::days(days).map(|_| ()),
+            Self::Date { year, month, day } => Self::date(year, month, day).map(|_| ()),
+        }
+    }
+
+    /// Returns the point in time the limit refers to, 
+    /// counting days back from `now`.
+    /// Dates refer to midnight UTC at the start of that day.
+    pub fn cutoff(&self, now: SystemTime) -> SystemTime {
+        match *self {
+            Self::Days(days) => now.checked_sub(Duration::from_secs(days as u64 * Self::SECONDS_PER_DAY)).unwrap_or(UNIX_EPOCH),
+            Self::Date { year, month, day } => {
+                let days = Self::days_from_civil(year as i64, month as i64, day as i64);
+                if days >= 0 {
+                    UNIX_EPOCH + Duration::from_secs(days as u64 * Self::SECONDS_PER_DAY)
+                } else {
+                    UNIX_EPOCH - Duration::from_secs(days.unsigned_abs() * Self::SECONDS_PER_DAY)
+                }
+            }
+        }
+    }
+
+    fn is_leap_year(year: u16) -> bool {
+        (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400)
+    }
+
+    fn days_in_month(year: u16, month: u8) -> u8 {
+        match month {
+            2 if Self::is_leap_year(year) => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        }
+    }
+
+    /// Days since 1970-01-01 of a proleptic Gregorian date
+    pub(crate) fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+        let year = if month <= 2 { year - 1 } else { year };
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146_097 + day_of_era - 719_468
+    }
+
+    /// Proleptic Gregorian date of a number of days since 1970-01-01
+    pub(crate) fn civil_from_days(days: i64) -> (i64, i64, i64) {
+        let days = days + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days - era * 146_097;
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_portion = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_portion + 2) / 5 + 1;
+        let month = if month_portion < 10 { month_portion + 3 } else { month_portion - 9 };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+        (year, month, day)
+    }
+}
+
+impl TryFrom<Duration> for AgeLimit {
+    type Error = &'static str;
+
+    /// Whole days of the duration, any remainder is dropped.
+    fn try_from(duration: Duration) -> Result<Self, Self::Error> {
+        Self::days((duration.as_secs() / Self::SECONDS_PER_DAY).try_into().unwrap_or(u32::MAX))
+    }
+}
+
+impl TryFrom<SystemTime> for AgeLimit {
+    type Error = &'static str;
+
+    /// The UTC calendar date of the point in time.
+    fn try_from(time: SystemTime) -> Result<Self, Self::Error> {
+        let days = match time.duration_since(UNIX_EPOCH) {
+            Ok(since) => (since.as_secs() / Self::SECONDS_PER_DAY) as i64,
+            Err(before) => -(before.duration().as_secs().div_ceil(Self::SECONDS_PER_DAY) as i64),
+        };
+        let (year, month, day) = Self::civil_from_days(days);
+
+        Self::date(year.try_into().map_err(|_| "The year of an age limit must be between 1900 and 9999.")?, month as u8, day as u8)
+    }
+}
+
+impl FromStr for AgeLimit {
+    type Err = &'static str;
+
+    /// Parses the robocopy notation, either `n` days or `YYYYMMDD`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() || !s.chars().all(|c| c.is_ascii_digit()) {
+            return Err("An age limit must be a number of days or a date in the form YYYYMMDD.");
+        }
+
+        match s.parse::<u32>() {
+            Ok(days) if days < 1900 => Ok(Self::Days(days)),
+            _ if s.len() == 8 => Self::date(s[0..4].parse().unwrap(), s[4..6].parse().unwrap(), s[6..8].parse().unwrap()),
+            _ => Err("An age limit must be a number of days or a date in the form YYYYMMDD."),
+        }
+    }
+}
+
+impl From<&AgeLimit> for OsString {
+    fn from(al: &AgeLimit) -> Self {
+        OsString::from(match al {
+            AgeLimit::Days(days) => format!("{}", days),
+            AgeLimit::Date { year, month, day } => format!("{:04}{:02}{:02}", year, month, day),
+        })
+    }
+}
+impl From<AgeLimit> for OsString {
+    fn from(al: AgeLimit) -> Self {
+        (&al).into()
+    }
+}
+
 /// Handles all filter attributes supported by Robocopy
 #[derive(Debug, Clone, Default)]
-pub struct Filter<'a> {
+pub struct Filter {
+    /// Only copy files with the archive attribute set
+    pub archive_only: bool,
+    /// Only copy files with the archive attribute set and clear it on the source
     pub handle_archive_and_reset: bool,
     pub include_only_files_with_any_of_these_attribs: Option<FileAttributes>,
     
@@ -353,22 +532,39 @@ pub struct Filter<'a> {
 
     pub file_exclusion_filter_exceptions: Option<FileExclusionFilterException>,
     
-    pub max_size: Option<u128>,
-    pub min_size: Option<u128>,
+    pub max_size: Option<ByteSize>,
+    pub min_size: Option<ByteSize>,
 
-    pub max_age: Option<&'a str>,
-    pub min_age: Option<&'a str>,
+    pub max_age: Option<AgeLimit>,
+    pub min_age: Option<AgeLimit>,
     
-    pub max_last_access_date: Option<&'a str>,
-    pub min_last_access_date: Option<&'a str>,
+    pub max_last_access_date: Option<AgeLimit>,
+    pub min_last_access_date: Option<AgeLimit>,
+}
+
+impl Filter {
+    /// Checks that the size and age limits are valid and do not contradict each other.
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if let (Some(max_size), Some(min_size)) = (self.max_size, self.min_size) {
+            if min_size > max_size {
+                return Err("The minimum size is larger than the maximum size.");
+            }
+        }
+
+        [self.max_age, self.min_age, self.max_last_access_date, self.min_last_access_date].iter()
+            .flatten()
+            .try_for_each(AgeLimit::validate)
+    }
 }
 
-impl<'a> From<&'a Filter<'a>> for Vec<OsString> {
-    fn from(filter: &'a Filter<'a>) -> Self {
+impl From<&Filter> for Vec<OsString> {
+    fn from(filter: &Filter) -> Self {
         let mut res = Vec::new();
         
         if filter.handle_archive_and_reset {
             res.push(OsString::from("/m"));
+        } else if filter.archive_only {
+            res.push(OsString::from("/a"));
         }
         if let Some(attribs) = filter.include_only_files_with_any_of_these_attribs {
             res.push(OsString::from(String::from("/ia:") + Into::<OsString>::into(attribs).to_str().unwrap()));
@@ -389,31 +585,31 @@ impl<'a> From<&'a Filter<'a>> for Vec<OsString> {
         }
 
         if let Some(max_size) = filter.max_size {
-            res.push(OsString::from(format!("/max:{}", max_size)));
+            res.push(OsString::from(String::from("/max:") + Into::<OsString>::into(max_size).to_str().unwrap()));
         }
         if let Some(min_size) = filter.min_size {
-            res.push(OsString::from(format!("/min:{}", min_size)));
+            res.push(OsString::from(String::from("/min:") + Into::<OsString>::into(min_size).to_str().unwrap()));
         }
         
         if let Some(max_age) = filter.max_age {
-            res.push(OsString::from(format!("/maxage:{}", max_age)));
+            res.push(OsString::from(String::from("/maxage:") + Into::<OsString>::into(max_age).to_str().unwrap()));
         }
         if let Some(min_age) = filter.min_age {
-            res.push(OsString::from(format!("/minage:{}", min_age)));
+            res.push(OsString::from(String::from("/minage:") + Into::<OsString>::into(min_age).to_str().unwrap()));
         }
 
         if let Some(max_lad) = filter.max_last_access_date {
-            res.push(OsString::from(format!("/maxlad:{}", max_lad)));
+            res.push(OsString::from(String::from("/maxlad:") + Into::<OsString>::into(max_lad).to_str().unwrap()));
         }
         if let Some(min_lad) = filter.min_last_access_date {
-            res.push(OsString::from(format!("/minlad:{}", min_lad)));
+            res.push(OsString::from(String::from("/minlad:") + Into::<OsString>::into(min_lad).to_str().unwrap()));
         }
 
         res
     }
 }
-impl<'a> From<Filter<'a>> for Vec<OsString> {
-    fn from(filter: Filter<'a>) -> Self {
+impl From<Filter> for Vec<OsString> {
+    fn from(filter: Filter) -> Self {
         (&filter).into()
     }
 }
\ No newline at end of file
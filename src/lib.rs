@@ -24,12 +24,36 @@ pub mod filter;
 pub mod performance;
 pub mod logging;
 pub mod exit_codes;
-
-use std::{convert::{TryFrom, TryInto}, ffi::OsString, ops::Add, path::Path, process::Command};
+pub mod size;
+pub mod wildcard;
+pub mod evaluate;
+pub mod classify;
+pub mod summary;
+pub mod plan;
+pub mod native;
+mod pool;
+pub mod throttle;
+pub mod restart;
+pub mod attributes;
+pub mod log;
+pub mod verify;
+pub mod manifest;
+pub mod security;
+pub mod owner;
+pub mod monitor;
+pub mod cli;
+
+use std::{convert::{TryFrom, TryInto}, ffi::OsString, ops::Add, path::Path, process::Command, str::FromStr, sync::Arc};
+use attributes::{AttributeMapping, AttributeProvider};
+use verify::Verification;
+use owner::OwnerMapping;
+use monitor::{Monitor, MonitorSettings};
 use exit_codes::{ErrExitCode, OkExitCode};
 use filter::Filter;
-use performance::{PerformanceOptions, RetrySettings};
+use performance::{IoSettings, PerformanceOptions, RetrySettings};
 use logging::LoggingSettings;
+use plan::CopyPlan;
+use native::RunReport;
 
 /// For enums that allow for multiple variants to be 
 /// joined into a single variant
@@ -85,7 +109,7 @@ impl From<&FileProperties> for OsString {
             FileProperties::OWNER_INFO => "/copy:O",
             FileProperties::AUDITING_INFO => "/copy:U",
             FileProperties::_MULTIPLE(props) => {
-                let part = ['D', 'A', 'T', 'S', 'O', 'U'].iter().zip(props.iter()).filter(|(_, exists)| **exists).into_iter().unzip::<&char, &bool, String, Vec<bool>>().0;
+                let part = ['D', 'A', 'T', 'S', 'O', 'U'].iter().zip(props.iter()).filter(|(_, exists)| **exists).unzip::<&char, &bool, String, Vec<bool>>().0;
                 full = String::from("/copy:") + part.as_str();
                 full.as_str()
             }
@@ -102,7 +126,7 @@ impl MultipleVariant for FileProperties {
     fn single_variants(&self) -> Vec<Self> {
         match self {
             Self::_MULTIPLE(props) => {
-                Self::VARIANTS.iter().zip(props.iter()).filter(|(_, exists)| **exists).into_iter().unzip::<&Self, &bool, Vec<Self>, Vec<bool>>().0
+                Self::VARIANTS.iter().zip(props.iter()).filter(|(_, exists)| **exists).unzip::<&Self, &bool, Vec<Self>, Vec<bool>>().0
             },
             prop => vec![*prop],
         }
@@ -131,6 +155,23 @@ impl FileProperties {
         }
     }
 
+    /// Returns the properties as flags in the order of the `_MULTIPLE` variant.
+    pub fn flags(&self) -> [bool; 6] {
+        match self {
+            Self::_MULTIPLE(props) => *props,
+            prop => {
+                let mut props = [false; 6];
+                props[prop.index_of().unwrap()] = true;
+                props
+            }
+        }
+    }
+
+    /// Whether the property is set.
+    pub fn contains(&self, prop: Self) -> bool {
+        prop.index_of().is_some_and(|index| self.flags()[index])
+    }
+
     /// Returns a variant containing all available file properties.
     #[allow(unused)]
     pub fn all() -> Self {
@@ -144,6 +185,15 @@ impl FileProperties {
     }
 }
 
+impl FromStr for FileProperties {
+    type Err = &'static str;
+
+    /// Parses the letters of `/copy`, such as `DAT`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        flags_from_letters(s, ['D', 'A', 'T', 'S', 'O', 'U']).map(Self::_MULTIPLE).ok_or("File properties are letters of DATSOU.")
+    }
+}
+
 
 /// The directory Properties
 /// Default is both Data and Attributes
@@ -186,7 +236,7 @@ impl From<&DirectoryProperties> for OsString {
             DirectoryProperties::ATTRIBUTES => "/dcopy:A",
             DirectoryProperties::TIME_STAMPS => "/dcopy:T",
             DirectoryProperties::_MULTIPLE(props) => {
-                let part = ['D', 'A', 'T'].iter().zip(props.iter()).filter(|(_, exists)| **exists).into_iter().unzip::<&char, &bool, String, Vec<bool>>().0;
+                let part = ['D', 'A', 'T'].iter().zip(props.iter()).filter(|(_, exists)| **exists).unzip::<&char, &bool, String, Vec<bool>>().0;
                 full = String::from("/dcopy:") + part.as_str();
                 full.as_str()
             }
@@ -203,7 +253,7 @@ impl MultipleVariant for DirectoryProperties {
     fn single_variants(&self) -> Vec<Self> {
         match self {
             Self::_MULTIPLE(props) => {
-                Self::VARIANTS.iter().zip(props.iter()).filter(|(_, exists)| **exists).into_iter().unzip::<&Self, &bool, Vec<Self>, Vec<bool>>().0
+                Self::VARIANTS.iter().zip(props.iter()).filter(|(_, exists)| **exists).unzip::<&Self, &bool, Vec<Self>, Vec<bool>>().0
             },
             prop => vec![*prop],
         }
@@ -226,6 +276,23 @@ impl DirectoryProperties {
         }
     }
 
+    /// Returns the properties as flags in the order of the `_MULTIPLE` variant.
+    pub fn flags(&self) -> [bool; 3] {
+        match self {
+            Self::_MULTIPLE(props) => *props,
+            prop => {
+                let mut props = [false; 3];
+                props[prop.index_of().unwrap()] = true;
+                props
+            }
+        }
+    }
+
+    /// Whether the property is set.
+    pub fn contains(&self, prop: Self) -> bool {
+        prop.index_of().is_some_and(|index| self.flags()[index])
+    }
+
     /// Returns a variant containing all available directory properties.
     #[allow(unused)]
     pub fn all() -> Self {
@@ -239,6 +306,15 @@ impl DirectoryProperties {
     }
 }
 
+impl FromStr for DirectoryProperties {
+    type Err = &'static str;
+
+    /// Parses the letters of `/dcopy`, such as `DA`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        flags_from_letters(s, ['D', 'A', 'T']).map(Self::_MULTIPLE).ok_or("Directory properties are letters of DAT.")
+    }
+}
+
 
 #[allow(non_camel_case_types)]
 #[derive(Debug, Copy, Clone)]
@@ -289,7 +365,7 @@ impl From<&FileAttributes> for OsString {
             FileAttributes::ENCRYPTED => "E",
             FileAttributes::TEMPORARY => "T",
             FileAttributes::_MULTIPLE(props) => {
-                part = ['R', 'A', 'S', 'H', 'C', 'N', 'E', 'T'].iter().zip(props.iter()).filter(|(_, exists)| **exists).into_iter().unzip::<&char, &bool, String, Vec<bool>>().0;
+                part = ['R', 'A', 'S', 'H', 'C', 'N', 'E', 'T'].iter().zip(props.iter()).filter(|(_, exists)| **exists).unzip::<&char, &bool, String, Vec<bool>>().0;
                 part.as_str()
             }
         })
@@ -305,7 +381,7 @@ impl MultipleVariant for FileAttributes {
     fn single_variants(&self) -> Vec<Self> {
         match self {
             Self::_MULTIPLE(attribs) => {
-                Self::VARIANTS.iter().zip(attribs.iter()).filter(|(_, exists)| **exists).into_iter().unzip::<&Self, &bool, Vec<Self>, Vec<bool>>().0
+                Self::VARIANTS.iter().zip(attribs.iter()).filter(|(_, exists)| **exists).unzip::<&Self, &bool, Vec<Self>, Vec<bool>>().0
             },
             attrib => vec![*attrib],
         }
@@ -338,6 +414,28 @@ impl FileAttributes {
         }
     }
 
+    /// Returns the attributes as flags in the order of the `_MULTIPLE` variant.
+    pub fn flags(&self) -> [bool; 8] {
+        match self {
+            Self::_MULTIPLE(attribs) => *attribs,
+            attrib => {
+                let mut attribs = [false; 8];
+                attribs[attrib.index_of().unwrap()] = true;
+                attribs
+            }
+        }
+    }
+
+    /// Whether any of the attributes of other are set.
+    pub fn contains_any(&self, other: &Self) -> bool {
+        self.flags().iter().zip(other.flags().iter()).any(|(a, b)| *a && *b)
+    }
+
+    /// Whether all of the attributes of other are set.
+    pub fn contains_all(&self, other: &Self) -> bool {
+        self.flags().iter().zip(other.flags().iter()).all(|(a, b)| *a || !*b)
+    }
+
     /// Returns a variant containing all available file attributes.
     #[allow(unused)]
     pub fn all() -> Self {
@@ -351,6 +449,24 @@ impl FileAttributes {
     }
 }
 
+impl FromStr for FileAttributes {
+    type Err = &'static str;
+
+    /// Parses the letters of `/ia`, `/xa`, `/a+` and `/a-`, such as `RH`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        flags_from_letters(s, ['R', 'A', 'S', 'H', 'C', 'N', 'E', 'T']).map(Self::_MULTIPLE).ok_or("File attributes are letters of RASHCNET.")
+    }
+}
+
+/// Sets the flag of each letter, in any case, none if a letter is not one of them
+fn flags_from_letters<const N: usize>(s: &str, letters: [char; N]) -> Option<[bool; N]> {
+    let mut flags = [false; N];
+    for c in s.chars() {
+        flags[letters.iter().position(|letter| letter.eq_ignore_ascii_case(&c))?] = true;
+    }
+    Some(flags)
+}
+
 
 #[allow(non_camel_case_types)]
 #[derive(Debug, Clone, Copy)]
@@ -476,7 +592,8 @@ pub enum FilesystemOptions {
     FAT_FILE_NAMES,
     ASSUME_FAT_FILE_TIMES,
     DISABLE_LONG_PATHS,
-    _MULTIPLE([bool; 3])
+    COMPENSATE_DST,
+    _MULTIPLE([bool; 4])
 }
 
 impl From<&FilesystemOptions> for Vec<OsString> {
@@ -485,7 +602,8 @@ impl From<&FilesystemOptions> for Vec<OsString> {
             FilesystemOptions::FAT_FILE_NAMES => vec![OsString::from("/fat")],
             FilesystemOptions::ASSUME_FAT_FILE_TIMES => vec![OsString::from("/fft")],
             FilesystemOptions::DISABLE_LONG_PATHS => vec![OsString::from("/256")],
-            FilesystemOptions::_MULTIPLE(options) => ["/fat", "/fft", "/256"].iter().zip(options.iter()).filter(|(_, exists)| **exists).map(|(option, _)| OsString::from(*option)).collect()
+            FilesystemOptions::COMPENSATE_DST => vec![OsString::from("/dst")],
+            FilesystemOptions::_MULTIPLE(options) => ["/fat", "/fft", "/256", "/dst"].iter().zip(options.iter()).filter(|(_, exists)| **exists).map(|(option, _)| OsString::from(*option)).collect()
         }
     }
 }
@@ -495,6 +613,44 @@ impl From<FilesystemOptions> for Vec<OsString> {
     }
 }
 
+impl FilesystemOptions {
+    fn index_of(&self) -> Option<usize> {
+        match self {
+            Self::FAT_FILE_NAMES => Some(0),
+            Self::ASSUME_FAT_FILE_TIMES => Some(1),
+            Self::DISABLE_LONG_PATHS => Some(2),
+            Self::COMPENSATE_DST => Some(3),
+            _ => None,
+        }
+    }
+
+    /// Returns the options as flags in the order of the `_MULTIPLE` variant.
+    pub fn flags(&self) -> [bool; 4] {
+        match self {
+            Self::_MULTIPLE(options) => *options,
+            option => {
+                let mut options = [false; 4];
+                options[option.index_of().unwrap()] = true;
+                options
+            }
+        }
+    }
+
+    /// Whether the option is set.
+    pub fn contains(&self, option: Self) -> bool {
+        option.index_of().is_some_and(|index| self.flags()[index])
+    }
+}
+
+
+/// How a command is executed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Engine {
+    /// Run the robocopy executable
+    External,
+    /// Copy in process with `std::fs`, see the native module
+    Native,
+}
 
 /// Robocopy command Wrapper
 /// 
@@ -516,20 +672,33 @@ pub struct RobocopyCommand<'a> {
     pub copy_file_properties: Option<FileProperties>,
     pub copy_dir_properties: Option<DirectoryProperties>,
 
-    pub filter: Option<Filter<'a>>,
+    pub filter: Option<Filter>,
 
     pub filesystem_options: Option<FilesystemOptions>,
     pub performance_options: Option<PerformanceOptions>,
     pub retry_settings: Option<RetrySettings>,
+    pub io_settings: Option<IoSettings>,
     
     pub logging: Option<LoggingSettings<'a>>,
     
     pub mv: Option<Move>,
     pub post_copy_actions: Option<PostCopyActions>,
+    /// Keep copying whenever the source changed (`/mon` and `/mot`)
+    pub monitor: Option<MonitorSettings>,
+    /// How attributes map onto files without them in native execution, the default mapping if none
+    pub attribute_mapping: Option<AttributeMapping>,
+    /// Where filters read attributes from when planning and in native execution, the attribute mapping if none
+    pub attribute_provider: Option<Arc<dyn AttributeProvider>>,
+    /// Users and groups replaced when native execution copies ownership, owners keep their ids if none
+    pub owner_mapping: Option<OwnerMapping>,
+    /// Hash every copied file at the source and destination in native execution
+    pub verification: Option<Verification>,
 
     /// To use this option empty_dir_copy and PostCopyAction::RMV_FILES_AND_DIRS_NOT_IN_SRC must also be in use
     pub overwrite_destination_dir_sec_settings_when_mirror: bool,
-    // todo fix secfix and timfix
+    /// Fix the timestamps of files that are skipped too (`/timfix`)
+    pub fix_file_times: bool,
+    // todo fix secfix
     // todo job options
 }
 
@@ -551,93 +720,138 @@ impl<'a> Default for RobocopyCommand<'a> {
             filesystem_options: None,
             performance_options: None,
             retry_settings: None,
+            io_settings: None,
             logging: None,
             mv: None,
             post_copy_actions: None,
+            monitor: None,
+            attribute_mapping: None,
+            attribute_provider: None,
+            owner_mapping: None,
+            verification: None,
             overwrite_destination_dir_sec_settings_when_mirror: false,
+            fix_file_times: false,
         }
     }
 }
 
 impl<'a> RobocopyCommand<'a> {
+    /// Works out what the command would do without changing anything
+    pub fn plan(&self) -> std::io::Result<CopyPlan> {
+        CopyPlan::new(self)
+    }
+
+    /// Execute the command with the chosen engine
+    pub fn execute_with(&self, engine: Engine) -> Result<OkExitCode, Result<ErrExitCode, (&'static str, i8)>> {
+        match engine {
+            Engine::External => self.execute(),
+            Engine::Native => self.execute_native().exit_code(),
+        }
+    }
+
+    /// Execute the command in process without robocopy
+    pub fn execute_native(&self) -> RunReport {
+        native::execute(self)
+    }
+
+    /// Execute the command in process and keep copying whenever the source changes,
+    /// the first pass copies everything
+    pub fn monitor_native(&self) -> std::io::Result<Monitor<'_, 'a>> {
+        Monitor::new(self)
+    }
+
     /// Execute the command
     pub fn execute(&self) -> Result<OkExitCode, Result<ErrExitCode, (&'static str, i8)>>{
-        let mut command = Command::new("robocopy");
+        if let Some(filter) = &self.filter {
+            filter.validate().map_err(|err| Err((err, ErrExitCode::NO_CHANGE_FATAL_ERROR as i8)))?;
+        }
+
+        let exit_code = Command::new("robocopy").args(self.args()).status().expect("failed to execute robocopy")
+            .code().expect("Process terminated by signal") as i8;
         
-        command
-            .arg(self.source)
-            .arg(self.destination);
+        OkExitCode::try_from(exit_code)
+    }
 
-        self.files.iter().for_each(|file| {command.arg(file);});
+    /// The arguments robocopy is executed with
+    pub fn args(&self) -> Vec<OsString> {
+        let mut args = vec![OsString::from(self.source), OsString::from(self.destination)];
+
+        self.files.iter().for_each(|file| args.push(OsString::from(file)));
 
         if let Some(mode) = &self.copy_mode {
-            command.arg(Into::<OsString>::into(mode));
+            args.push(Into::<OsString>::into(mode));
         }
         if self.unbuffered {
-            command.arg("/j");
+            args.push(OsString::from("/j"));
         }
         
         if self.empty_dir_copy && 
                 self.remove_files_and_dirs_not_in_src && 
                 self.overwrite_destination_dir_sec_settings_when_mirror {
-            command.arg("/mir");
-            command.arg("/e");
+            args.push(OsString::from("/mir"));
+            args.push(OsString::from("/e"));
         } else {
             if self.empty_dir_copy {
-                command.arg("/e");
+                args.push(OsString::from("/e"));
             } else {
-                command.arg("/s");
+                args.push(OsString::from("/s"));
             }
             
             if self.remove_files_and_dirs_not_in_src {
-                command.arg("/purge");
+                args.push(OsString::from("/purge"));
             }
         }
 
         if let Some(n) = self.only_copy_top_n_levels {
-            command.arg(format!("/lev:{}", n));
+            args.push(OsString::from(format!("/lev:{}", n)));
         }
 
         if self.structure_and_size_zero_files_only {
-            command.arg("/create");
+            args.push(OsString::from("/create"));
         }
 
         if let Some(properties) = self.copy_file_properties {
-            command.arg(Into::<OsString>::into(properties));
+            args.push(Into::<OsString>::into(properties));
         }
         if let Some(properties) = self.copy_dir_properties {
-            command.arg(Into::<OsString>::into(properties));
+            args.push(Into::<OsString>::into(properties));
+        }
+        if self.fix_file_times {
+            args.push(OsString::from("/timfix"));
         }
         
         if let Some(filter) = &self.filter {
-            Into::<Vec<OsString>>::into(filter).into_iter().for_each(|arg| {command.arg(arg);});
+            Into::<Vec<OsString>>::into(filter).into_iter().for_each(|arg| args.push(arg));
         }
         if let Some(options) = &self.filesystem_options {
-            Into::<Vec<OsString>>::into(options).into_iter().for_each(|arg| {command.arg(arg);});
+            Into::<Vec<OsString>>::into(options).into_iter().for_each(|arg| args.push(arg));
         }        
         if let Some(options) = &self.performance_options {
-            Into::<Vec<OsString>>::into(options).into_iter().for_each(|arg| {command.arg(arg);});
+            Into::<Vec<OsString>>::into(options).into_iter().for_each(|arg| args.push(arg));
         }        
         if let Some(settings) = &self.retry_settings {
-            Into::<Vec<OsString>>::into(settings).into_iter().for_each(|arg| {command.arg(arg);});
+            Into::<Vec<OsString>>::into(settings).into_iter().for_each(|arg| args.push(arg));
+        }
+        if let Some(settings) = &self.io_settings {
+            Into::<Vec<OsString>>::into(settings).into_iter().for_each(|arg| args.push(arg));
         }
 
         if let Some(logging) = &self.logging {
-            command.arg(Into::<OsString>::into(logging));
+            args.push(Into::<OsString>::into(logging));
         }
 
         if let Some(mv) = &self.mv {
-            command.arg(Into::<OsString>::into(mv));
+            args.push(Into::<OsString>::into(mv));
         }
        
         if let Some(actions) = &self.post_copy_actions {
-            Into::<Vec<OsString>>::into(actions).into_iter().for_each(|arg| {command.arg(arg);});
+            Into::<Vec<OsString>>::into(actions).into_iter().for_each(|arg| args.push(arg));
+        }
+        if let Some(settings) = &self.monitor {
+            Into::<Vec<OsString>>::into(settings).into_iter().for_each(|arg| args.push(arg));
         }
 
-        let exit_code = command.status().expect("failed to execute robocopy")
-            .code().expect("Process terminated by signal") as i8;
-        
-        OkExitCode::try_from(exit_code)
+        args
     }
 }
 
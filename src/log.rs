@@ -0,0 +1,336 @@
+//! Robocopy-format logs
+//!
+//! Writes the log of a native run in the layout robocopy uses for `/log` and `/unilog`,
+//! so tools that read robocopy logs can read these as well.
+//! Times are written in UTC.
+
+use std::{collections::HashMap, fs::{self, File}, io::{self, Write}, path::{Path, MAIN_SEPARATOR}, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use crate::RobocopyCommand;
+use crate::evaluate::{FileClass, Rule};
+use crate::filter::AgeLimit;
+use crate::logging::LoggingSettings;
+use crate::native::{Outcome, RunEntry, RunReport};
+use crate::performance::RetrySettings;
+use crate::plan::Action;
+use crate::summary::Totals;
+
+const RULE: &str = "------------------------------------------------------------------------------";
+
+/// A log file opened for a run
+#[derive(Debug)]
+pub struct Log {
+    file: File,
+    unicode: bool,
+}
+
+impl Log {
+    /// Opens the log file of the settings, appending to it or replacing it.
+    /// A new unicode log starts with a byte order mark.
+    pub fn open(settings: &LoggingSettings<'_>) -> io::Result<Self> {
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .append(settings.append)
+            .truncate(!settings.append)
+            .open(settings.log)?;
+
+        if settings.unicode && file.metadata()?.len() == 0 {
+            file.write_all(&[0xFF, 0xFE])?;
+        }
+
+        Ok(Self {
+            file,
+            unicode: settings.unicode,
+        })
+    }
+
+    /// Writes the header, the entries and the summary of a run
+    pub fn write_run(&mut self, command: &RobocopyCommand<'_>, report: &RunReport) -> io::Result<()> {
+        self.write(&text(command, report))
+    }
+
+    /// Writes text with robocopy's line endings and encoding
+    fn write(&mut self, text: &str) -> io::Result<()> {
+        let text = text.replace('\n', "\r\n");
+
+        if self.unicode {
+            let bytes: Vec<u8> = text.encode_utf16().flat_map(u16::to_le_bytes).collect();
+            self.file.write_all(&bytes)
+        } else {
+            self.file.write_all(text.as_bytes())
+        }
+    }
+}
+
+/// The header, the entries and the summary of a run, as robocopy prints them
+pub fn text(command: &RobocopyCommand<'_>, report: &RunReport) -> String {
+    let started = report.started.unwrap_or_else(SystemTime::now);
+    let ended = report.ended.unwrap_or_else(SystemTime::now);
+
+    let mut text = header(command, started);
+    match &report.fatal_error {
+        Some(error) => text += &error_lines(ended, "Accessing Source Directory", &full_path(command.source, Path::new(""), true), error),
+        None => {
+            text += &entries(command, report, ended);
+            text += &summary(report, started, ended);
+        },
+    }
+    text
+}
+
+/// Reads the text of a log, unicode logs start with a byte order mark
+pub fn read(path: &Path) -> io::Result<String> {
+    let bytes = fs::read(path)?;
+
+    Ok(match bytes.strip_prefix(&[0xFF, 0xFE]) {
+        Some(utf16) => String::from_utf16_lossy(&utf16.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect::<Vec<u16>>()),
+        None => String::from_utf8_lossy(&bytes).into_owned(),
+    })
+}
+
+fn header(command: &RobocopyCommand<'_>, started: SystemTime) -> String {
+    let files = if command.files.is_empty() { vec!["*.*"] } else { command.files.clone() };
+
+    let mut text = format!("\n{}-\n   ROBOCOPY     ::     Robust File Copy for Windows                              \n{}-\n\n", RULE, RULE);
+    text += &format!("  Started : {}\n", long_date(started));
+    text += &format!("   Source : {}\n", full_path(command.source, Path::new(""), true));
+    text += &format!("     Dest : {}\n\n", full_path(command.destination, Path::new(""), true));
+    text += &format!("    Files : {}\n", files.join("\n\t    "));
+
+    if let Some(filter) = &command.filter {
+        let excluded_files = filter.file_exclusion_filter.as_ref().map(|filter| filter.wildcards());
+        let excluded_dirs = filter.directory_exclusion_filter.as_ref().map(|filter| filter.wildcards());
+
+        for (label, set) in [("Exc Files", excluded_files), (" Exc Dirs", excluded_dirs)] {
+            if let Some(set) = set.filter(|set| !set.is_empty()) {
+                let patterns: Vec<&str> = set.patterns().iter().map(|pattern| pattern.as_str()).collect();
+                text += &format!("\n{} : {}\n", label, patterns.join("\n\t    "));
+            }
+        }
+    }
+
+    let mut options: Vec<String> = command.args().iter().skip(2 + command.files.len())
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect();
+    if let Some(logging) = &command.logging {
+        let logging = std::ffi::OsString::from(logging).to_string_lossy().into_owned();
+        options.retain(|option| *option != logging);
+    }
+    let retry_settings = command.retry_settings.unwrap_or_default();
+    if retry_settings.specify_retries_failed_copies.is_none() {
+        options.push(format!("/R:{}", RetrySettings::DEFAULT_RETRIES));
+    }
+    if retry_settings.specify_wait_between_retries.is_none() {
+        options.push(format!("/W:{}", RetrySettings::DEFAULT_WAIT));
+    }
+
+    text += &format!("\t    \n  Options : {} {} \n\n{}\n\n", files.join(" "), options.join(" "), RULE);
+    text
+}
+
+/// The directory and file lines in plan order, with the errors of failed entries.
+/// Files and directories that were excluded or are the same are not listed,
+/// like robocopy does without `/v`, except for special files that were skipped.
+fn entries(command: &RobocopyCommand<'_>, report: &RunReport, now: SystemTime) -> String {
+    let retries = command.retry_settings.unwrap_or_default();
+    let mut text = String::new();
+    let mut purged_dirs: Vec<&Path> = Vec::new();
+
+    let mut source_files: HashMap<&Path, i64> = HashMap::new();
+    for entry in report.entries.iter().map(|run_entry| &run_entry.entry).filter(|entry| !entry.is_dir && entry.source.is_some()) {
+        if let Some(parent) = entry.path.parent() {
+            *source_files.entry(parent).or_insert(0) += 1;
+        }
+    }
+
+    for run_entry in &report.entries {
+        let entry = &run_entry.entry;
+        if purged_dirs.iter().any(|dir| entry.path.starts_with(dir)) {
+            continue;
+        }
+
+        if entry.is_dir {
+            let line = match (&entry.class, &entry.action) {
+                (FileClass::Extra, action) => {
+                    if *action == Action::Purge {
+                        purged_dirs.push(&entry.path);
+                    }
+                    Some(("*EXTRA Dir", -1, full_path(command.destination, &entry.path, true)))
+                },
+                (_, Action::Skip(Some(_))) | (FileClass::Lonely, Action::Skip(None)) => None,
+                (class, _) => {
+                    let files = source_files.get(entry.path.as_path()).copied().unwrap_or(0);
+                    let label = if *class == FileClass::Lonely { "New Dir" } else { "" };
+                    Some((label, files, full_path(command.source, &entry.path, true)))
+                },
+            };
+
+            if let Some((label, count, path)) = line {
+                text += &format!("\t{:<10}{:>10}\t{}\n", format!("  {}", label), count, path);
+            }
+        } else if let Some(label) = file_label(run_entry) {
+            let name = entry.path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+            text += &format!("\t    {:<10}\t\t{:>8}\t{}\n", label, size_label(entry.size(), 1), name);
+        }
+
+        for (attempt, error) in run_entry.failed_attempts.iter().enumerate() {
+            let path = match entry.action {
+                Action::Purge => full_path(command.destination, &entry.path, entry.is_dir),
+                _ => full_path(command.source, &entry.path, entry.is_dir),
+            };
+            text += &error_lines(now, operation(run_entry), &path, error);
+
+            let last = attempt + 1 == run_entry.failed_attempts.len();
+            if !last || run_entry.outcome == Outcome::Done {
+                text += &format!("Waiting {} seconds... Retrying...\n", retries.wait().as_secs());
+            } else if matches!(run_entry.outcome, Outcome::Failed(_)) && attempt >= retries.retries() {
+                text += "\nERROR: RETRY LIMIT EXCEEDED.\n\n";
+            }
+        }
+    }
+
+    text
+}
+
+/// The class robocopy lists a file with, none if it is not listed
+fn file_label(run_entry: &RunEntry) -> Option<&'static str> {
+    let entry = &run_entry.entry;
+
+    match (&entry.class, &entry.action) {
+        (FileClass::Extra, Action::Skip(Some(_))) => None,
+        (FileClass::Extra, _) => Some("*EXTRA File"),
+        (FileClass::Mismatched, _) => Some("*Mismatch"),
+        (_, Action::Skip(Some(Rule::SpecialFile))) => Some("*Special"),
+        (class, Action::Copy) => Some(match class {
+            FileClass::Lonely => "New File",
+            FileClass::Newer => "Newer",
+            FileClass::Older => "Older",
+            FileClass::Changed => "Changed",
+            FileClass::Tweaked => "Tweaked",
+            FileClass::Modified => "Modified",
+            _ => "same",
+        }),
+        _ if run_entry.outcome.is_failure() => Some(""),
+        _ => None,
+    }
+}
+
+/// What robocopy was doing when an entry failed
+fn operation(run_entry: &RunEntry) -> &'static str {
+    match (&run_entry.entry.action, run_entry.entry.is_dir) {
+        (Action::Purge, true) => "Deleting Extra Directory",
+        (Action::Purge, false) => "Deleting Extra File",
+        (_, true) => "Creating Destination Directory",
+        (_, false) => "Copying File",
+    }
+}
+
+/// An error line with the OS error code of the message, followed by the message
+fn error_lines(now: SystemTime, operation: &str, path: &str, error: &str) -> String {
+    let (code, message) = match error.rfind(" (os error ") {
+        Some(index) if error.ends_with(')') => (
+            error[index + 11..error.len() - 1].parse::<u32>().unwrap_or(0),
+            &error[..index],
+        ),
+        _ => (0, error),
+    };
+
+    format!("\n{} ERROR {} (0x{:08X}) {} {}\n{}\n", short_date(now), code, code, operation, path, message)
+}
+
+fn summary(report: &RunReport, started: SystemTime, ended: SystemTime) -> String {
+    let row = |label: &str, totals: &Totals, size: bool| {
+        let values = [totals.total, totals.copied, totals.skipped, totals.mismatch, totals.failed, totals.extras];
+        let columns: String = values.iter()
+            .map(|value| format!("{:>10}", if size { size_label(*value, 2) } else { value.to_string() }))
+            .collect();
+        format!("{:>8} :{}\n", label, columns)
+    };
+    let total_time = ended.duration_since(started).unwrap_or_default();
+
+    let mut text = format!("\n{}\n\n", RULE);
+    text += &format!("{:>10}{:>10}{:>10}{:>10}{:>10}{:>10}{:>10}\n", "", "Total", "Copied", "Skipped", "Mismatch", "FAILED", "Extras");
+    text += &row("Dirs", &report.summary.dirs, false);
+    text += &row("Files", &report.summary.files, false);
+    text += &row("Bytes", &report.summary.bytes, true);
+    text += &format!("   Times :{:>10}{:>10}{:>20}{:>10}{:>10}\n\n", clock(total_time), clock(report.elapsed), "", clock(Duration::ZERO), clock(Duration::ZERO));
+
+    if report.bytes_transferred > 0 {
+        let throughput = report.throughput();
+        text += &format!("\n   Speed :{:>22} Bytes/sec.\n", thousands(throughput));
+        text += &format!("   Speed :{:>22.3} MegaBytes/min.\n", throughput as f64 * 60.0 / (1024.0 * 1024.0));
+    }
+    text += &format!("   Ended : {}\n\n", long_date(ended));
+    text
+}
+
+/// A path below a root, directories end with a separator
+fn full_path(root: &Path, path: &Path, is_dir: bool) -> String {
+    let mut full = root.join(path).to_string_lossy().into_owned();
+    if is_dir && !full.ends_with(MAIN_SEPARATOR) {
+        full.push(MAIN_SEPARATOR);
+    }
+    full
+}
+
+/// A size in bytes, or in k, m, g or t with the given number of decimals from 1 KiB up
+fn size_label(bytes: u64, decimals: usize) -> String {
+    const UNITS: [&str; 4] = ["k", "m", "g", "t"];
+
+    if bytes < 1024 {
+        return bytes.to_string();
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.*} {}", decimals, value, UNITS[unit])
+}
+
+/// A number with thousands separators
+fn thousands(value: u64) -> String {
+    let digits = value.to_string();
+    let mut text = String::new();
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index).is_multiple_of(3) {
+            text.push(',');
+        }
+        text.push(digit);
+    }
+    text
+}
+
+/// A duration as hours, minutes and seconds
+fn clock(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// Date and time of day in UTC
+fn civil(time: SystemTime) -> (i64, i64, i64, u64, i64) {
+    let secs = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let days = (secs / 86_400) as i64;
+    let (year, month, day) = AgeLimit::civil_from_days(days);
+    (year, month, day, secs % 86_400, days)
+}
+
+/// A date like `Sunday, October 18, 2026 3:33:00 PM`
+fn long_date(time: SystemTime) -> String {
+    const WEEKDAYS: [&str; 7] = ["Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday"];
+    const MONTHS: [&str; 12] = ["January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November", "December"];
+
+    let (year, month, day, secs, days) = civil(time);
+    let hour = secs / 3600;
+    format!("{}, {} {}, {} {}:{:02}:{:02} {}",
+        WEEKDAYS[(days + 4).rem_euclid(7) as usize], MONTHS[month as usize - 1], day, year,
+        (hour + 11) % 12 + 1, secs / 60 % 60, secs % 60, if hour < 12 { "AM" } else { "PM" })
+}
+
+/// A date like `2026/10/18 15:33:00`
+fn short_date(time: SystemTime) -> String {
+    let (year, month, day, secs, _) = civil(time);
+    format!("{}/{:02}/{:02} {:02}:{:02}:{:02}", year, month, day, secs / 3600, secs / 60 % 60, secs % 60)
+}
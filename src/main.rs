@@ -0,0 +1,139 @@
+//! `robocopyrs`, the command line of robocopy on every platform
+//!
+//! Parses robocopy arguments, runs them with robocopy or natively
+//! and exits with the exit code robocopy returns or would have returned.
+
+use std::{env, process::{self, Command}};
+
+use serde_json::{json, Value};
+
+use robocopyrs::{cli, log, Engine, RobocopyCommand};
+use robocopyrs::exit_codes::ErrExitCode;
+use robocopyrs::native::RunReport;
+use robocopyrs::summary::Summary;
+
+const FATAL: i32 = ErrExitCode::NO_CHANGE_FATAL_ERROR as i32;
+
+const USAGE: &str = "\
+Usage :: robocopyrs <source> <destination> [<file>[ ...]] [<options>] [--native | --external] [--json]
+
+    source      :: Source Directory.
+    destination :: Destination Directory.
+    file        :: File(s) to copy (names/wildcards: default is \"*.*\").
+    options     :: The options of robocopy, such as /mir /xf *.tmp /MT:16.
+
+    --native    :: Copy in process, the default on platforms other than Windows.
+    --external  :: Run robocopy, the default on Windows.
+    --json      :: Print the exit code and the summary as JSON.
+";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.is_empty() || args.iter().any(|arg| arg == "/?" || arg == "--help") {
+        print!("{}", USAGE);
+        process::exit(if args.is_empty() { FATAL } else { 0 });
+    }
+
+    let invocation = match cli::parse(&args) {
+        Ok(invocation) => invocation,
+        Err(err) => fail(&format!("ERROR : {}", err)),
+    };
+    if let Some(Err(err)) = invocation.command.filter.as_ref().map(|filter| filter.validate()) {
+        fail(&format!("ERROR : {}", err));
+    }
+
+    process::exit(match invocation.engine {
+        Engine::External => external(&invocation.command, invocation.json),
+        Engine::Native if invocation.command.monitor.is_some() => monitor(&invocation.command, invocation.json),
+        Engine::Native => native(&invocation.command, invocation.json),
+    })
+}
+
+/// Prints an error and the usage and exits like robocopy does for a fatal error
+fn fail(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    process::exit(FATAL)
+}
+
+/// Runs robocopy. For JSON its output is read rather than printed,
+/// the summary is taken from the log if the command has one.
+fn external(command: &RobocopyCommand<'_>, json: bool) -> i32 {
+    let mut robocopy = Command::new("robocopy");
+    robocopy.args(command.args());
+
+    if !json {
+        return match robocopy.status() {
+            Ok(status) => status.code().unwrap_or(FATAL),
+            Err(err) => {
+                eprintln!("ERROR : robocopy could not be started. {}", err);
+                FATAL
+            },
+        };
+    }
+
+    let output = match robocopy.output() {
+        Ok(output) => output,
+        Err(err) => {
+            print_json("external", FATAL, None, Some(&format!("robocopy could not be started. {}", err)), None);
+            return FATAL;
+        },
+    };
+    let code = output.status.code().unwrap_or(FATAL);
+    let text = match &command.logging {
+        Some(logging) => log::read(logging.log).unwrap_or_default(),
+        None => String::from_utf8_lossy(&output.stdout).into_owned(),
+    };
+
+    print_json("external", code, Summary::from_log(&text).as_ref(), None, None);
+    code
+}
+
+/// Copies in process and prints the run like robocopy, unless it is logged to a file
+fn native(command: &RobocopyCommand<'_>, json: bool) -> i32 {
+    let report = command.execute_native();
+    print_report(command, &report, json, None);
+    report.exit_code_bits() as i32
+}
+
+/// Copies in process whenever the source changes, until the process is ended
+fn monitor(command: &RobocopyCommand<'_>, json: bool) -> i32 {
+    let monitor = match command.monitor_native() {
+        Ok(monitor) => monitor,
+        Err(err) => fail(&format!("ERROR : The source cannot be monitored. {}", err)),
+    };
+
+    let mut code = 0;
+    for pass in monitor {
+        match pass {
+            Ok(pass) => {
+                print_report(command, &pass.report, json, Some(pass.number));
+                code = pass.report.exit_code_bits() as i32;
+            },
+            Err(err) => fail(&format!("ERROR : Monitoring the source failed. {}", err)),
+        }
+    }
+    code
+}
+
+fn print_report(command: &RobocopyCommand<'_>, report: &RunReport, json: bool, pass: Option<usize>) {
+    if json {
+        print_json("native", report.exit_code_bits() as i32, Some(&report.summary), report.fatal_error.as_deref(), pass);
+    } else if command.logging.is_none() {
+        print!("{}", log::text(command, report));
+    }
+}
+
+/// Prints the summary of a run as a line of JSON
+fn print_json(engine: &str, exit_code: i32, summary: Option<&Summary>, error: Option<&str>, pass: Option<usize>) {
+    let mut value = json!({
+        "engine": engine,
+        "exit_code": exit_code,
+        "summary": summary.map(Summary::to_json),
+        "error": error,
+    });
+    if let (Some(pass), Value::Object(object)) = (pass, &mut value) {
+        object.insert(String::from("pass"), json!(pass));
+    }
+
+    println!("{}", value);
+}
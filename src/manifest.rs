@@ -0,0 +1,338 @@
+//! Run manifests
+//!
+//! A manifest records every file and directory a run touched and what happened to it,
+//! together with the arguments, the start and end time and the exit status of the run.
+//! It is built from the report of a native run or from a robocopy log,
+//! and written as JSON.
+
+use std::{convert::TryFrom, fs, io, path::{Path, PathBuf}, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use serde_json::{json, Value};
+
+use crate::{log, RobocopyCommand};
+use crate::filter::AgeLimit;
+use crate::native::{Outcome, RunReport};
+use crate::plan::Action;
+
+/// What happened to an entry of a manifest
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestAction {
+    Copied,
+    Skipped,
+    Purged,
+    Moved,
+    Failed,
+}
+
+impl ManifestAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Copied => "copied",
+            Self::Skipped => "skipped",
+            Self::Purged => "purged",
+            Self::Moved => "moved",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+/// A file or directory of a manifest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// Path relative to the source and destination roots
+    pub path: PathBuf,
+    pub is_dir: bool,
+    pub action: ManifestAction,
+    pub size: u64,
+    pub source_modified: Option<SystemTime>,
+    pub destination_modified: Option<SystemTime>,
+    /// Hash of the copied file, if it was verified
+    pub hash: Option<String>,
+    /// The rule that excluded a skipped entry or the error of a failed one
+    pub reason: Option<String>,
+}
+
+/// The record of a run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Manifest {
+    pub arguments: Vec<String>,
+    pub started: Option<SystemTime>,
+    pub ended: Option<SystemTime>,
+    /// The exit code robocopy returned or would have returned
+    pub exit_code: i8,
+    pub entries: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    /// The manifest of a native run.
+    /// The destination times of copied entries are read after the run.
+    pub fn from_report(command: &RobocopyCommand<'_>, report: &RunReport) -> Self {
+        let entries = report.entries.iter().map(|run_entry| {
+            let entry = &run_entry.entry;
+            let action = match (&run_entry.outcome, &entry.action) {
+                (outcome, _) if outcome.is_failure() => ManifestAction::Failed,
+                (Outcome::Done, Action::Copy) if command.mv.is_some() && !entry.is_dir => ManifestAction::Moved,
+                (Outcome::Done, Action::Copy) => ManifestAction::Copied,
+                (Outcome::Done, Action::Purge) => ManifestAction::Purged,
+                _ => ManifestAction::Skipped,
+            };
+            let destination_modified = match action {
+                ManifestAction::Copied | ManifestAction::Moved => fs::metadata(command.destination.join(&entry.path)).and_then(|metadata| metadata.modified()).ok(),
+                _ => entry.destination.map(|metadata| metadata.modified),
+            };
+            let reason = match (&run_entry.outcome, &entry.action) {
+                (Outcome::Failed(error), _) => Some(error.clone()),
+                (Outcome::HashMismatch(mismatch), _) => Some(mismatch.to_string()),
+                (_, Action::Skip(Some(rule))) => Some(rule.to_string()),
+                _ => None,
+            };
+
+            ManifestEntry {
+                path: entry.path.clone(),
+                is_dir: entry.is_dir,
+                action,
+                size: entry.size(),
+                source_modified: entry.source.map(|metadata| metadata.modified),
+                destination_modified,
+                hash: run_entry.digest.as_ref().map(ToString::to_string),
+                reason,
+            }
+        }).collect();
+
+        Self {
+            arguments: command.args().iter().map(|arg| arg.to_string_lossy().into_owned()).collect(),
+            started: report.started,
+            ended: report.ended,
+            exit_code: report.exit_code_bits(),
+            entries,
+        }
+    }
+
+    /// The manifest of a run from its robocopy log, see [`Manifest::from_log`]
+    pub fn read_log(path: &Path, exit_code: i8) -> io::Result<Self> {
+        Ok(Self::from_log(&log::read(path)?, exit_code))
+    }
+
+    /// The manifest of a run from the text of its robocopy log, as written with `/log`.
+    /// The log only lists what robocopy lists without `/v`, has no modification times
+    /// and gives sizes from 1 KiB up rounded, so those entries are less complete than native ones.
+    /// Times are read as UTC.
+    pub fn from_log(log: &str, exit_code: i8) -> Self {
+        let mut manifest = Self {
+            exit_code,
+            ..Self::default()
+        };
+        let (mut source, mut destination) = (String::new(), String::new());
+        let (mut moving, mut purging) = (false, false);
+        let mut directory = PathBuf::new();
+        // the action of the last entry before an error marked it failed
+        let mut before_error = None;
+        // robocopy lists a file again when it retries it
+        let mut retrying = false;
+
+        for line in log.lines() {
+            let trimmed = line.trim();
+
+            if let Some(value) = header_value(line, "Started") {
+                manifest.started = parse_long_date(value);
+            } else if let Some(value) = header_value(line, "Ended") {
+                manifest.ended = parse_long_date(value);
+            } else if let Some(value) = header_value(line, "Source") {
+                source = value.to_string();
+            } else if let Some(value) = header_value(line, "Dest") {
+                destination = value.to_string();
+            } else if let Some(value) = header_value(line, "Options") {
+                manifest.arguments = value.split_whitespace().map(String::from).collect();
+                let has = |switch: &str| manifest.arguments.iter().any(|argument| argument.eq_ignore_ascii_case(switch));
+                moving = has("/mov") || has("/move");
+                purging = has("/purge") || has("/mir");
+            } else if trimmed.starts_with("Waiting ") && trimmed.ends_with("Retrying...") {
+                retrying = true;
+                if let (Some(entry), Some(action)) = (manifest.entries.last_mut(), before_error.take()) {
+                    entry.action = action;
+                    entry.reason = None;
+                }
+            } else if let Some(error) = error_line(trimmed) {
+                if let Some(entry) = manifest.entries.last_mut() {
+                    if entry.action != ManifestAction::Failed {
+                        before_error = Some(entry.action);
+                    }
+                    entry.action = ManifestAction::Failed;
+                    entry.reason = Some(error.to_string());
+                }
+            } else if line.starts_with('\t') {
+                let fields: Vec<&str> = line.split('\t').collect();
+                let label = fields.get(1).map(|field| field.trim()).unwrap_or("");
+
+                match fields.len() {
+                    // directory: label and file count, full path
+                    3 => {
+                        let path = fields[2].trim();
+                        let label = label.trim_end_matches(|c: char| c.is_ascii_digit() || c == '-').trim();
+                        let (extra, root) = if label.starts_with("*EXTRA") { (true, &destination) } else { (false, &source) };
+                        let relative = relative_path(path, root);
+
+                        manifest.entries.push(ManifestEntry {
+                            path: relative.clone(),
+                            is_dir: true,
+                            action: match (extra, label) {
+                                (true, _) if purging => ManifestAction::Purged,
+                                (false, "New Dir") => ManifestAction::Copied,
+                                _ => ManifestAction::Skipped,
+                            },
+                            size: 0,
+                            source_modified: None,
+                            destination_modified: None,
+                            hash: None,
+                            reason: None,
+                        });
+                        before_error = None;
+                        retrying = false;
+                        directory = relative;
+                    },
+                    // file: label, size, name
+                    5 => {
+                        let action = match label {
+                            "*EXTRA File" if purging => ManifestAction::Purged,
+                            "*EXTRA File" | "*Mismatch" | "*Special" | "" => ManifestAction::Skipped,
+                            _ if moving => ManifestAction::Moved,
+                            _ => ManifestAction::Copied,
+                        };
+
+                        let path = directory.join(fields[4].trim());
+                        match manifest.entries.last_mut() {
+                            Some(retried) if retrying && !retried.is_dir && retried.path == path => retried.action = action,
+                            _ => manifest.entries.push(ManifestEntry {
+                                path,
+                                is_dir: false,
+                                action,
+                                size: parse_size(fields[3].trim()),
+                                source_modified: None,
+                                destination_modified: None,
+                                hash: None,
+                                reason: None,
+                            }),
+                        }
+                        before_error = None;
+                        retrying = false;
+                    },
+                    _ => (),
+                }
+            }
+        }
+
+        manifest
+    }
+
+    /// The manifest as JSON, times as RFC 3339 in UTC
+    pub fn to_json(&self) -> Value {
+        let time = |time: Option<SystemTime>| time.map(rfc3339);
+
+        json!({
+            "arguments": self.arguments,
+            "started": time(self.started),
+            "ended": time(self.ended),
+            "exit_code": self.exit_code,
+            "entries": self.entries.iter().map(|entry| json!({
+                "path": entry.path.to_string_lossy(),
+                "is_dir": entry.is_dir,
+                "action": entry.action.as_str(),
+                "size": entry.size,
+                "source_modified": time(entry.source_modified),
+                "destination_modified": time(entry.destination_modified),
+                "hash": entry.hash,
+                "reason": entry.reason,
+            })).collect::<Vec<Value>>(),
+        })
+    }
+
+    /// Writes the manifest as JSON to a file
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(&self.to_json())?)
+    }
+}
+
+/// The value of a header line like `  Started : ...`
+fn header_value<'l>(line: &'l str, name: &str) -> Option<&'l str> {
+    let (key, value) = line.split_once(" : ")?;
+    if key.trim() == name && line.starts_with(' ') {
+        Some(value.trim())
+    } else {
+        None
+    }
+}
+
+/// The message of an error line like `2026/10/18 15:33:00 ERROR 5 (0x00000005) Copying File ...`
+fn error_line(line: &str) -> Option<&str> {
+    let index = line.find(" ERROR ")?;
+    line[..index].starts_with(|c: char| c.is_ascii_digit()).then(|| &line[index + 1..])
+}
+
+/// Path of a full path below a root, the full path if it is not below it.
+/// Logs written by robocopy have Windows paths, so both separators count
+/// and the root matches regardless of case.
+fn relative_path(path: &str, root: &str) -> PathBuf {
+    fn components(path: &str) -> impl Iterator<Item = &str> {
+        path.split(['/', '\\']).filter(|component| !component.is_empty())
+    }
+
+    let mut rest = components(path);
+    if root.is_empty() || !components(root).all(|root| rest.next().is_some_and(|component| component.to_lowercase() == root.to_lowercase())) {
+        return PathBuf::from(path);
+    }
+    rest.collect()
+}
+
+/// A size as robocopy logs it, bytes or a number with a `k`, `m`, `g` or `t` unit
+pub(crate) fn parse_size(size: &str) -> u64 {
+    let (number, unit) = size.split_once(' ').unwrap_or((size, ""));
+    let factor = match unit {
+        "k" => 1u64 << 10,
+        "m" => 1 << 20,
+        "g" => 1 << 30,
+        "t" => 1 << 40,
+        _ => 1,
+    };
+    number.parse::<f64>().map(|number| (number * factor as f64).round() as u64).unwrap_or(0)
+}
+
+/// A date like `Sunday, October 18, 2026 3:33:00 PM`
+fn parse_long_date(date: &str) -> Option<SystemTime> {
+    const MONTHS: [&str; 12] = ["January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November", "December"];
+
+    let (_, date) = date.split_once(", ")?;
+    let parts: Vec<&str> = date.split_whitespace().collect();
+    if parts.len() != 5 {
+        return None;
+    }
+
+    let month = MONTHS.iter().position(|month| *month == parts[0])? as i64 + 1;
+    let day: i64 = parts[1].trim_end_matches(',').parse().ok()?;
+    let year: i64 = parts[2].parse().ok()?;
+    let clock: Vec<u64> = parts[3].split(':').map(str::parse).collect::<Result<_, _>>().ok()?;
+    let (hour, minute, second) = match clock[..] {
+        [hour, minute, second] if (1..=12).contains(&hour) => (hour % 12, minute, second),
+        _ => return None,
+    };
+    let hour = match parts[4] {
+        "AM" => hour,
+        "PM" => hour + 12,
+        _ => return None,
+    };
+
+    let days = u64::try_from(AgeLimit::days_from_civil(year, month, day)).ok()?;
+    Some(UNIX_EPOCH + Duration::from_secs(days * 86_400 + hour * 3600 + minute * 60 + second))
+}
+
+/// A time like `2026-10-18T15:33:00.5Z`
+fn rfc3339(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (year, month, day) = AgeLimit::civil_from_days((secs / 86_400) as i64);
+
+    let mut text = format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}", year, month, day, secs / 3600 % 24, secs / 60 % 60, secs % 60);
+    if since_epoch.subsec_nanos() > 0 {
+        text += format!(".{:09}", since_epoch.subsec_nanos()).trim_end_matches('0');
+    }
+    text + "Z"
+}
@@ -0,0 +1,344 @@
+//! Monitoring the source
+//!
+//! With `/mon` and `/mot` robocopy keeps running after the first copy and copies again
+//! once the source changed. Native execution watches the source with inotify,
+//! waits until the changes settled and copies only the directories they were in.
+//! Each copy is a [`Pass`], monitoring goes on until it is stopped with a [`StopHandle`].
+
+use std::{collections::BTreeSet, ffi::OsString, io, path::{Path, PathBuf}, sync::{Arc, atomic::{AtomicBool, Ordering}}, thread, time::{Duration, Instant}};
+
+use crate::{native, RobocopyCommand};
+use crate::evaluate::Evaluator;
+use crate::logging::LoggingSettings;
+use crate::native::RunReport;
+
+/// When to copy again after the source changed
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MonitorSettings {
+    /// `/mon`, copy again when more than this many changes were seen
+    pub changes: Option<usize>,
+    /// `/mot`, copy again after this many minutes if anything changed
+    pub minutes: Option<usize>,
+    /// How long the source must be quiet before copying again, in native execution only
+    pub debounce: Option<Duration>,
+}
+
+impl MonitorSettings {
+    /// Quiet time before copying again when `debounce` is not given
+    pub const DEFAULT_DEBOUNCE: Duration = Duration::from_secs(1);
+
+    pub fn debounce(&self) -> Duration {
+        self.debounce.unwrap_or(Self::DEFAULT_DEBOUNCE)
+    }
+
+    /// Whether enough changed, long enough ago, for another pass
+    fn due(&self, changes: usize, since_pass: Duration) -> bool {
+        changes > self.changes.unwrap_or(0) &&
+            self.minutes.is_none_or(|minutes| since_pass >= Duration::from_secs(minutes as u64 * 60))
+    }
+}
+
+impl From<&MonitorSettings> for Vec<OsString> {
+    fn from(settings: &MonitorSettings) -> Self {
+        let mut res = Vec::new();
+
+        if let Some(changes) = settings.changes {
+            res.push(OsString::from(format!("/mon:{}", changes)));
+        }
+        if let Some(minutes) = settings.minutes {
+            res.push(OsString::from(format!("/mot:{}", minutes)));
+        }
+
+        res
+    }
+}
+impl From<MonitorSettings> for Vec<OsString> {
+    fn from(settings: MonitorSettings) -> Self {
+        (&settings).into()
+    }
+}
+
+/// Stops a [`Monitor`] from another thread,
+/// a pass that is running is finished first
+#[derive(Debug, Clone, Default)]
+pub struct StopHandle(Arc<AtomicBool>);
+
+impl StopHandle {
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// One copy of a monitored source
+#[derive(Debug, Clone)]
+pub struct Pass {
+    /// The first pass copies everything and is number 0
+    pub number: usize,
+    /// The changed paths that started the pass, relative to the source
+    pub changed: Vec<PathBuf>,
+    /// The directories that were copied again, relative to the source
+    pub directories: Vec<PathBuf>,
+    /// What the pass did, with paths relative to the source
+    pub report: RunReport,
+}
+
+/// Copies a source again whenever it changed.
+/// The passes are the items of the iterator, which ends once the monitor is stopped.
+pub struct Monitor<'c, 'a> {
+    command: &'c RobocopyCommand<'a>,
+    settings: MonitorSettings,
+    evaluator: Evaluator,
+    watcher: Watcher,
+    stop: StopHandle,
+    passes: usize,
+    last_pass: Instant,
+}
+
+impl<'c, 'a> Monitor<'c, 'a> {
+    /// How often the monitor looks for changes and whether it was stopped
+    const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+    /// Starts watching the source, the settings of the command or the defaults if it has none
+    pub fn new(command: &'c RobocopyCommand<'a>) -> io::Result<Self> {
+        let evaluator = Evaluator::from(command);
+        let mut watcher = Watcher::new()?;
+        watcher.watch_tree(command.source, Path::new(""), &evaluator)?;
+
+        Ok(Self {
+            command,
+            settings: command.monitor.unwrap_or_default(),
+            evaluator,
+            watcher,
+            stop: StopHandle::default(),
+            passes: 0,
+            last_pass: Instant::now(),
+        })
+    }
+
+    /// A handle that stops the monitor
+    pub fn stop_handle(&self) -> StopHandle {
+        self.stop.clone()
+    }
+
+    /// Waits until enough changed and the changes settled,
+    /// returns the changed paths or none if the monitor was stopped
+    fn wait_for_changes(&mut self) -> io::Result<Option<BTreeSet<PathBuf>>> {
+        let mut changed = BTreeSet::new();
+        let mut last_change = None;
+
+        loop {
+            if self.stop.is_stopped() {
+                return Ok(None);
+            }
+            if self.watcher.read(self.command.source, &self.evaluator, &mut changed)? {
+                last_change = Some(Instant::now());
+            }
+
+            let settled = last_change.is_some_and(|last_change: Instant| last_change.elapsed() >= self.settings.debounce());
+            if settled && self.settings.due(changed.len(), self.last_pass.elapsed()) {
+                return Ok(Some(changed));
+            }
+            thread::sleep(Self::POLL_INTERVAL);
+        }
+    }
+
+    /// Copies the directories the changes were in, with their subdirectories
+    fn copy_changed(&self, changed: &BTreeSet<PathBuf>) -> (Vec<PathBuf>, RunReport) {
+        let directories = affected_directories(self.command.source, changed);
+        let logging = self.command.logging.map(|logging| LoggingSettings { append: true, ..logging });
+
+        let mut report = RunReport::default();
+        for dir in &directories {
+            let levels = self.command.only_copy_top_n_levels.map(|levels| levels.saturating_sub(dir.components().count()));
+            if levels == Some(0) {
+                continue;
+            }
+
+            let (source, destination) = (self.command.source.join(dir), self.command.destination.join(dir));
+            let command = RobocopyCommand {
+                source: &source,
+                destination: &destination,
+                only_copy_top_n_levels: levels,
+                logging,
+                ..self.command.clone()
+            };
+            merge(&mut report, native::execute(&command), dir);
+        }
+
+        (directories, report)
+    }
+}
+
+impl<'c, 'a> Iterator for Monitor<'c, 'a> {
+    type Item = io::Result<Pass>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (changed, directories, report) = if self.passes == 0 {
+            (Vec::new(), vec![PathBuf::new()], native::execute(self.command))
+        } else {
+            let changed = match self.wait_for_changes() {
+                Ok(Some(changed)) => changed,
+                Ok(None) => return None,
+                Err(err) => return Some(Err(err)),
+            };
+            let (directories, report) = self.copy_changed(&changed);
+            (changed.into_iter().collect(), directories, report)
+        };
+
+        let pass = Pass { number: self.passes, changed, directories, report };
+        self.passes += 1;
+        self.last_pass = Instant::now();
+        Some(Ok(pass))
+    }
+}
+
+/// The directories that hold the changed paths, or their closest parent that still exists,
+/// without the ones inside another of them
+fn affected_directories(source: &Path, changed: &BTreeSet<PathBuf>) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = changed.iter().map(|path| {
+        let mut dir = path.parent().unwrap_or_else(|| Path::new(""));
+        while !dir.as_os_str().is_empty() && !source.join(dir).is_dir() {
+            dir = dir.parent().unwrap_or_else(|| Path::new(""));
+        }
+        dir.to_path_buf()
+    }).collect();
+    dirs.sort();
+    dirs.dedup();
+
+    let nested = |dir: &PathBuf| dirs.iter().any(|other| other != dir && dir.starts_with(other));
+    dirs.iter().filter(|dir| !nested(dir)).cloned().collect()
+}
+
+/// Adds the report of a directory to the report of a pass
+fn merge(report: &mut RunReport, mut part: RunReport, dir: &Path) {
+    for entry in &mut part.entries {
+        entry.entry.path = dir.join(&entry.entry.path);
+    }
+    if report.started.is_none() {
+        report.started = part.started;
+    }
+
+    report.entries.append(&mut part.entries);
+    report.summary += part.summary;
+    report.fatal_error = report.fatal_error.take().or(part.fatal_error);
+    report.elapsed += part.elapsed;
+    report.bytes_transferred += part.bytes_transferred;
+    report.ended = part.ended.or(report.ended);
+    report.log_error = report.log_error.take().or(part.log_error);
+    report.degraded.append(&mut part.degraded);
+}
+
+#[cfg(target_os = "linux")]
+use std::{collections::HashMap, fs};
+#[cfg(target_os = "linux")]
+use inotify::{EventMask, Inotify, WatchDescriptor, WatchMask};
+
+/// Watches the directories of the source with inotify
+#[cfg(target_os = "linux")]
+struct Watcher {
+    inotify: Inotify,
+    /// The watched directories relative to the source
+    dirs: HashMap<WatchDescriptor, PathBuf>,
+}
+
+#[cfg(target_os = "linux")]
+impl Watcher {
+    fn new() -> io::Result<Self> {
+        Ok(Self { inotify: Inotify::init()?, dirs: HashMap::new() })
+    }
+
+    /// Watches a directory and the directories below it that are not excluded
+    fn watch_tree(&mut self, source: &Path, dir: &Path, evaluator: &Evaluator) -> io::Result<()> {
+        let mask = WatchMask::MODIFY | WatchMask::ATTRIB | WatchMask::CLOSE_WRITE | WatchMask::CREATE |
+            WatchMask::DELETE | WatchMask::MOVED_FROM | WatchMask::MOVED_TO | WatchMask::ONLYDIR;
+        let descriptor = match self.inotify.watches().add(source.join(dir), mask) {
+            Ok(descriptor) => descriptor,
+            // removed before it could be watched
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        self.dirs.insert(descriptor, dir.to_path_buf());
+
+        for entry in fs::read_dir(source.join(dir))? {
+            let entry = entry?;
+            let path = dir.join(entry.file_name());
+            if entry.file_type()?.is_dir() && evaluator.check_path(&path, true).is_ok() {
+                self.watch_tree(source, &path, evaluator)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Adds the paths of the events that arrived so far to the changed paths,
+    /// ignoring the ones that are excluded. Returns whether there were any.
+    fn read(&mut self, source: &Path, evaluator: &Evaluator, changed: &mut BTreeSet<PathBuf>) -> io::Result<bool> {
+        let mut buffer = [0; 4096];
+        let mut any = false;
+
+        loop {
+            let events = match self.inotify.read_events(&mut buffer) {
+                Ok(events) => events,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(any),
+                Err(err) => return Err(err),
+            };
+
+            let mut created = Vec::new();
+            for event in events {
+                if event.mask.contains(EventMask::Q_OVERFLOW) {
+                    // events were lost, so everything may have changed
+                    changed.insert(PathBuf::new());
+                    any = true;
+                    continue;
+                }
+                if event.mask.contains(EventMask::IGNORED) {
+                    self.dirs.remove(&event.wd);
+                    continue;
+                }
+
+                let (dir, name) = match (self.dirs.get(&event.wd), event.name) {
+                    (Some(dir), Some(name)) => (dir, name),
+                    _ => continue,
+                };
+                let path = dir.join(name);
+                let is_dir = event.mask.contains(EventMask::ISDIR);
+                if evaluator.check_path(&path, is_dir).is_err() {
+                    continue;
+                }
+
+                if is_dir && event.mask.intersects(EventMask::CREATE | EventMask::MOVED_TO) {
+                    created.push(path.clone());
+                }
+                changed.insert(path);
+                any = true;
+            }
+
+            for dir in created {
+                self.watch_tree(source, &dir, evaluator)?;
+            }
+        }
+    }
+}
+
+/// Monitoring needs inotify, elsewhere it cannot start
+#[cfg(not(target_os = "linux"))]
+struct Watcher;
+
+#[cfg(not(target_os = "linux"))]
+impl Watcher {
+    fn new() -> io::Result<Self> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "Monitoring the source needs inotify, which this platform does not have."))
+    }
+
+    fn watch_tree(&mut self, _source: &Path, _dir: &Path, _evaluator: &Evaluator) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn read(&mut self, _source: &Path, _evaluator: &Evaluator, _changed: &mut BTreeSet<PathBuf>) -> io::Result<bool> {
+        Ok(false)
+    }
+}
@@ -0,0 +1,762 @@
+//! Native execution
+//!
+//! Executes a command with `std::fs` instead of the robocopy executable,
+//! so the same command definitions also work where robocopy is not available.
+//! A run returns the same exit code robocopy would have returned.
+
+use std::{convert::TryFrom, fs::{self, File}, io::{self, Read, Seek, SeekFrom, Write}, path::Path, sync::{Mutex, atomic::{AtomicU64, Ordering}}, thread, time::{Duration, Instant, SystemTime}};
+
+use crate::{pool, CopyMode, DirectoryProperties, FileAttributes, FileProperties, Move, PostCopyActions, RobocopyCommand};
+use crate::attributes::AttributeMapping;
+use crate::performance::PerformanceChoice;
+use crate::exit_codes::{ErrExitCode, OkExitCode};
+use crate::log::Log;
+use crate::owner::OwnerLookup;
+use crate::evaluate::FileClass;
+use crate::plan::{Action, CopyPlan, PlanEntry};
+use crate::summary::Summary;
+use crate::restart::{self, RestartMarker};
+use crate::security::{Degradation, SecurityInfo};
+use crate::throttle::TokenBucket;
+use crate::verify::{Digest, HashMismatch};
+
+/// What happened to an entry of the plan
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Done,
+    Skipped,
+    Failed(String),
+    /// The file was copied but the copy does not match the source
+    HashMismatch(HashMismatch),
+}
+
+impl Outcome {
+    /// Whether the entry failed, in which case it counts as FAILED
+    pub fn is_failure(&self) -> bool {
+        matches!(self, Self::Failed(_) | Self::HashMismatch(_))
+    }
+}
+
+/// An entry of the plan and what happened to it
+#[derive(Debug, Clone)]
+pub struct RunEntry {
+    pub entry: PlanEntry,
+    pub outcome: Outcome,
+    /// The errors of the attempts that failed, in order, including the retried ones
+    pub failed_attempts: Vec<String>,
+    /// The hash of the copied file if it was verified
+    pub digest: Option<Digest>,
+}
+
+impl RunEntry {
+    /// Adds the entry to the totals of a summary, failures are moved to the failed column
+    pub fn tally(&self, summary: &mut Summary) {
+        self.entry.tally(summary);
+
+        if self.outcome.is_failure() {
+            let size = self.entry.size();
+            let copied = self.entry.action == Action::Copy;
+
+            if self.entry.is_dir {
+                summary.dirs.failed += 1;
+                if copied {
+                    summary.dirs.copied -= 1;
+                }
+            } else {
+                summary.files.failed += 1;
+                summary.bytes.failed += size;
+                if copied {
+                    summary.files.copied -= 1;
+                    summary.bytes.copied -= size;
+                }
+            }
+        }
+    }
+}
+
+/// The result of a native run
+#[derive(Debug, Clone, Default)]
+pub struct RunReport {
+    pub entries: Vec<RunEntry>,
+    pub summary: Summary,
+    /// The error that stopped the run before anything was copied
+    pub fatal_error: Option<String>,
+    /// Time spent copying, purging and creating directories
+    pub elapsed: Duration,
+    /// Bytes of file data written to the destination
+    pub bytes_transferred: u64,
+    /// When the run started and ended
+    pub started: Option<SystemTime>,
+    pub ended: Option<SystemTime>,
+    /// The error that kept the log from being written
+    pub log_error: Option<String>,
+    /// Security information that was not copied because the destination does not support it
+    /// or the process is not permitted to set it, by path
+    pub degraded: Vec<Degradation>,
+}
+
+impl RunReport {
+    fn fatal(error: String) -> Self {
+        Self {
+            fatal_error: Some(error),
+            ..Self::default()
+        }
+    }
+
+    /// The exit code bitmask robocopy would have returned for the run
+    pub fn exit_code_bits(&self) -> i8 {
+        if self.fatal_error.is_some() {
+            return ErrExitCode::NO_CHANGE_FATAL_ERROR as i8;
+        }
+
+        let summary = &self.summary;
+        let mut bits = 0;
+        if summary.files.copied > 0 {
+            bits |= 1;
+        }
+        if summary.files.extras + summary.dirs.extras > 0 {
+            bits |= 2;
+        }
+        if summary.files.mismatch + summary.dirs.mismatch > 0 {
+            bits |= 4;
+        }
+        if summary.files.failed + summary.dirs.failed > 0 {
+            bits |= 8;
+        }
+        bits
+    }
+
+    /// The exit code robocopy would have returned for the run
+    pub fn exit_code(&self) -> Result<OkExitCode, Result<ErrExitCode, (&'static str, i8)>> {
+        OkExitCode::try_from(self.exit_code_bits())
+    }
+
+    /// The measured throughput in bytes per second
+    pub fn throughput(&self) -> u64 {
+        match self.elapsed.as_secs_f64() {
+            secs if secs > 0.0 => (self.bytes_transferred as f64 / secs) as u64,
+            _ => 0,
+        }
+    }
+
+    /// The entries that could not be copied or purged
+    pub fn failures(&self) -> impl Iterator<Item = &RunEntry> {
+        self.entries.iter().filter(|entry| entry.outcome.is_failure())
+    }
+
+    /// The copied files that did not match their source
+    pub fn hash_mismatches(&self) -> impl Iterator<Item = &RunEntry> {
+        self.entries.iter().filter(|entry| matches!(entry.outcome, Outcome::HashMismatch(_)))
+    }
+
+    /// The symbolic links that were left alone because they are excluded, broken or lead into a cycle
+    pub fn skipped_links(&self) -> impl Iterator<Item = &RunEntry> {
+        self.entries.iter().filter(|entry| matches!(&entry.entry.action, Action::Skip(Some(rule)) if rule.is_link_rule()))
+    }
+
+    /// The number of retries made over the whole run
+    pub fn retries(&self) -> usize {
+        self.entries.iter().map(|entry| match entry.outcome.is_failure() {
+            true => entry.failed_attempts.len().saturating_sub(1),
+            false => entry.failed_attempts.len(),
+        }).sum()
+    }
+}
+
+/// Executes a command natively and writes its log if it has `LoggingSettings`.
+/// A log that cannot be opened is a fatal error, like it is for robocopy.
+pub fn execute(command: &RobocopyCommand<'_>) -> RunReport {
+    let started = SystemTime::now();
+    let mut log = match command.logging.as_ref().map(Log::open).transpose() {
+        Ok(log) => log,
+        Err(err) => return RunReport::fatal(err.to_string()),
+    };
+
+    let mut report = run(command);
+    report.started = Some(started);
+    report.ended = Some(SystemTime::now());
+
+    if let Some(log) = &mut log {
+        if let Err(err) = log.write_run(command, &report) {
+            report.log_error = Some(err.to_string());
+        }
+    }
+
+    report
+}
+
+fn run(command: &RobocopyCommand<'_>) -> RunReport {
+    if let Some(filter) = &command.filter {
+        if let Err(err) = filter.validate() {
+            return RunReport::fatal(String::from(err));
+        }
+    }
+
+    let plan = match CopyPlan::new(command) {
+        Ok(plan) => plan,
+        Err(err) => return RunReport::fatal(err.to_string()),
+    };
+
+    let threads = command.performance_options.map(|options| options.thread_count()).unwrap_or(1);
+    let executor = Executor::new(command);
+    let start = Instant::now();
+    let executions = executor.run_all(&plan.entries, threads);
+
+    let mut report = RunReport {
+        elapsed: start.elapsed(),
+        bytes_transferred: executor.transferred.load(Ordering::Relaxed),
+        degraded: executor.degraded.into_inner().unwrap_or_else(|err| err.into_inner()),
+        ..RunReport::default()
+    };
+    report.degraded.sort_by(|a, b| a.path.cmp(&b.path));
+    for (entry, Execution { outcome, failed_attempts, digest }) in plan.entries.into_iter().zip(executions) {
+        let entry = RunEntry { entry, outcome, failed_attempts, digest };
+        entry.tally(&mut report.summary);
+        report.entries.push(entry);
+    }
+
+    report
+}
+
+/// What running an entry came to
+#[derive(Debug, Clone)]
+struct Execution {
+    outcome: Outcome,
+    failed_attempts: Vec<String>,
+    digest: Option<Digest>,
+}
+
+impl Execution {
+    fn new(outcome: Outcome, failed_attempts: Vec<String>) -> Self {
+        Self { outcome, failed_attempts, digest: None }
+    }
+}
+
+/// Applies the actions of plan entries to the file system
+struct Executor<'c, 'a> {
+    command: &'c RobocopyCommand<'a>,
+    file_properties: FileProperties,
+    dir_properties: DirectoryProperties,
+    mapping: AttributeMapping,
+    /// Limits the bandwidth of all copying threads together
+    limiter: Option<TokenBucket>,
+    /// Files smaller than this are not throttled
+    throttle_threshold: u64,
+    /// Size of each read and write when copying with a limit
+    chunk_size: usize,
+    /// Copy large files in chunks that can be resumed
+    restartable: bool,
+    /// Retries after a transient failure and the wait before each of them
+    retries: usize,
+    wait: Duration,
+    transferred: AtomicU64,
+    /// Recreate symbolic links instead of copying what they point to (`/sl`)
+    copy_links: bool,
+    /// Security information copied with files and directories
+    security: Vec<SecurityInfo>,
+    /// How owners are mapped if ownership is copied
+    owners: Option<OwnerLookup>,
+    degraded: Mutex<Vec<Degradation>>,
+}
+
+impl<'c, 'a> Executor<'c, 'a> {
+    /// Chunk size when throttling, robocopy pauses between blocks of this size for `/ipg`
+    const THROTTLED_CHUNK_SIZE: u64 = PerformanceChoice::INTER_PACKET_GAP_BLOCK;
+
+    fn new(command: &'c RobocopyCommand<'a>) -> Self {
+        let io_settings = command.io_settings.unwrap_or_default();
+        let bandwidth = [
+            command.performance_options.and_then(|options| options.performance_choice().bandwidth()),
+            io_settings.io_rate.map(|rate| rate.bytes()).filter(|rate| *rate > 0),
+        ].iter().flatten().min().copied();
+        let chunk_size = io_settings.max_io_size.map(|size| size.bytes()).filter(|size| *size > 0).unwrap_or(Self::THROTTLED_CHUNK_SIZE);
+        let file_properties = command.copy_file_properties.unwrap_or(FileProperties::_MULTIPLE([true, true, true, false, false, false]));
+        let security = [
+            (FileProperties::NTFS_ACCESS_CONTROL_LIST, SecurityInfo::AccessControl),
+            (FileProperties::AUDITING_INFO, SecurityInfo::Auditing),
+        ].iter().filter(|(property, _)| file_properties.contains(*property)).map(|(_, info)| *info).collect();
+
+        Self {
+            command,
+            limiter: bandwidth.map(|bytes_per_sec| TokenBucket::new(bytes_per_sec, chunk_size)),
+            throttle_threshold: io_settings.throttle_threshold.map(|size| size.bytes()).unwrap_or(0),
+            chunk_size: chunk_size.min(usize::MAX as u64) as usize,
+            restartable: matches!(command.copy_mode, Some(CopyMode::RESTARTABLE_MODE) | Some(CopyMode::RESTARTABLE_MODE_BACKUP_MODE_FALLBACK)),
+            transferred: AtomicU64::new(0),
+            copy_links: command.performance_options.is_some_and(|options| options.copies_links()),
+            security,
+            owners: match file_properties.contains(FileProperties::OWNER_INFO) {
+                true => Some(OwnerLookup::new(command.owner_mapping.clone().unwrap_or_default())),
+                false => None,
+            },
+            degraded: Mutex::new(Vec::new()),
+            retries: command.retry_settings.unwrap_or_default().retries(),
+            wait: command.retry_settings.unwrap_or_default().wait(),
+            file_properties,
+            dir_properties: command.copy_dir_properties.unwrap_or(DirectoryProperties::_MULTIPLE([true, true, false])),
+            mapping: command.attribute_mapping.unwrap_or_default(),
+        }
+    }
+
+    /// Runs the entries and returns their outcomes in the same order.
+    /// Purges and directories are handled first in plan order,
+    /// then the files are copied on up to `threads` threads.
+    /// Every entry is run by exactly one thread, so no destination has more than one writer.
+    /// The security information, attributes and times of directories are copied last.
+    fn run_all(&self, entries: &[PlanEntry], threads: usize) -> Vec<Execution> {
+        let mut outcomes: Vec<Option<Execution>> = vec![None; entries.len()];
+        let mut purged_dirs: Vec<&Path> = Vec::new();
+
+        for (entry, outcome) in entries.iter().zip(outcomes.iter_mut()) {
+            if entry.action == Action::Copy && !entry.is_dir {
+                continue;
+            }
+
+            let execution = if purged_dirs.iter().any(|dir| entry.path.starts_with(dir)) {
+                Execution::new(Outcome::Done, Vec::new())
+            } else {
+                self.run(entry)
+            };
+            if entry.is_dir && entry.action == Action::Purge && execution.outcome == Outcome::Done {
+                purged_dirs.push(&entry.path);
+            }
+            *outcome = Some(execution);
+        }
+
+        let files: Vec<usize> = outcomes.iter().enumerate().filter(|(_, outcome)| outcome.is_none()).map(|(index, _)| index).collect();
+        let copied = pool::map(&files, threads, |index| self.run(&entries[*index]));
+        files.into_iter().zip(copied).for_each(|(index, outcome)| outcomes[index] = Some(outcome));
+
+        let mut outcomes: Vec<Execution> = outcomes.into_iter().map(Option::unwrap).collect();
+        self.copy_dir_attributes(entries, &mut outcomes);
+        if self.dir_properties.contains(DirectoryProperties::TIME_STAMPS) {
+            self.copy_dir_times(entries, &mut outcomes);
+        }
+        if matches!(self.command.mv, Some(Move::FILES_AND_DIRS)) {
+            self.remove_source_dirs(entries, &mut outcomes);
+        }
+        outcomes
+    }
+
+    /// Runs an entry, retrying transient failures after waiting between the attempts.
+    /// A copy that does not match its source is copied once more if verification asks for it.
+    /// Directories the plan could not read fail without being run.
+    fn run(&self, entry: &PlanEntry) -> Execution {
+        if let Some(error) = &entry.error {
+            return Execution::new(Outcome::Failed(error.clone()), vec![error.clone()]);
+        }
+
+        let mut failed_attempts = Vec::new();
+        let mut recopied = false;
+
+        loop {
+            let result = match (&entry.action, entry.is_dir) {
+                (Action::Copy, true) => self.create_dir(&entry.path).map(|()| None),
+                (Action::Copy, false) => self.copy_file(entry),
+                (Action::Purge, true) => self.purge_dir(&entry.path).map(|()| None),
+                (Action::Purge, false) => self.purge_file(&entry.path).map(|()| None),
+                (Action::Skip(_), false) if self.fixes_times(entry) => self.fix_times(&entry.path).map(|()| None),
+                (Action::Skip(_), _) => return Execution::new(Outcome::Skipped, failed_attempts),
+            };
+
+            match result {
+                Ok(_) if matches!(entry.action, Action::Skip(_)) => return Execution::new(Outcome::Skipped, failed_attempts),
+                Ok(digest) => return Execution { digest, ..Execution::new(Outcome::Done, failed_attempts) },
+                Err(err) => {
+                    failed_attempts.push(err.to_string());
+                    if let Some(mismatch) = HashMismatch::of(&err) {
+                        if recopied || !self.command.verification.is_some_and(|verification| verification.recopy) {
+                            return Execution::new(Outcome::HashMismatch(mismatch.clone()), failed_attempts);
+                        }
+                        recopied = true;
+                        continue;
+                    }
+
+                    if !is_transient(&err) || failed_attempts.len() > self.retries {
+                        return Execution::new(Outcome::Failed(err.to_string()), failed_attempts);
+                    }
+                    thread::sleep(self.wait);
+                },
+            }
+        }
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        let destination = self.command.destination.join(path);
+        fs::create_dir_all(&destination)?;
+        self.copy_owner(&self.command.source.join(path), &destination)
+    }
+
+    /// Gives a created directory the security information, attributes and permissions of its source
+    fn set_dir_attributes(&self, path: &Path) -> io::Result<()> {
+        let (source, destination) = (self.command.source.join(path), self.command.destination.join(path));
+        self.copy_security(&source, &destination)?;
+
+        if self.dir_properties.contains(DirectoryProperties::ATTRIBUTES) {
+            self.mapping.copy(&source, &destination)?;
+            fs::set_permissions(&destination, fs::metadata(&source)?.permissions())?;
+        }
+        Ok(())
+    }
+
+    /// Copies the owner and group with `/copy:O`,
+    /// recording it if the process is not permitted to
+    fn copy_owner(&self, source: &Path, destination: &Path) -> io::Result<()> {
+        let mapping = match &self.owners {
+            Some(mapping) => mapping,
+            None => return Ok(()),
+        };
+
+        if let Some(err) = mapping.copy(source, destination)? {
+            self.degraded.lock().unwrap_or_else(|err| err.into_inner()).push(Degradation {
+                path: destination.to_path_buf(),
+                name: String::from("owner"),
+                reason: err.to_string(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Copies the security information of `/copy:S` and `/copy:U`,
+    /// recording what the destination could not take
+    fn copy_security(&self, source: &Path, destination: &Path) -> io::Result<()> {
+        for info in &self.security {
+            let degraded = info.copy(source, destination)?;
+            if !degraded.is_empty() {
+                self.degraded.lock().unwrap_or_else(|err| err.into_inner()).extend(degraded);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Copies a file, or moves it with `/mov` and `/move`.
+    /// A moved file is renamed when the source and destination are on the same file system,
+    /// otherwise it is copied and verified before the source is deleted.
+    /// A file an earlier attempt already renamed counts as moved.
+    /// Returns the hash of the copy when verifying.
+    fn copy_file(&self, entry: &PlanEntry) -> io::Result<Option<Digest>> {
+        let source_path = self.command.source.join(&entry.path);
+        let destination_path = self.command.destination.join(&entry.path);
+        if let Some(parent) = destination_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let moving = self.command.mv.is_some() && !self.command.structure_and_size_zero_files_only;
+        if self.copy_links && fs::symlink_metadata(&source_path)?.file_type().is_symlink() {
+            copy_link(&source_path, &destination_path)?;
+            if moving {
+                if fs::read_link(&destination_path)? != fs::read_link(&source_path)? {
+                    return Err(io::Error::other("The link was not recreated, the source was not deleted."));
+                }
+                fs::remove_file(&source_path)?;
+            }
+            return Ok(None);
+        }
+        if moving && (already_moved(entry, &source_path, &destination_path)? || rename(&source_path, &destination_path)?) {
+            RestartMarker::remove(&destination_path)?;
+            self.apply_post_copy_actions(&destination_path)?;
+            return self.command.verification.map(|verification| verification.algorithm.hash_file(&destination_path)).transpose();
+        }
+
+        let metadata = fs::metadata(&source_path)?;
+        let restartable = self.restartable && metadata.len() > restart::CHUNK_SIZE && !self.command.structure_and_size_zero_files_only;
+        let mut destination = open_file(&destination_path, !restartable)?;
+        if restartable {
+            let copied = self.copy_data_restartable(&mut File::open(&source_path)?, &mut destination, &destination_path, &metadata)?;
+            self.transferred.fetch_add(copied, Ordering::Relaxed);
+        } else if !self.command.structure_and_size_zero_files_only {
+            let copied = self.copy_data(&mut File::open(&source_path)?, &mut destination, metadata.len(), u64::MAX)?;
+            self.transferred.fetch_add(copied, Ordering::Relaxed);
+        }
+
+        if self.file_properties.contains(FileProperties::TIME_STAMPS) {
+            destination.set_times(file_times(&metadata)?)?;
+        }
+        drop(destination);
+        self.copy_owner(&source_path, &destination_path)?;
+        // before the permissions, a read-only file may refuse changes to its extended attributes
+        self.copy_security(&source_path, &destination_path)?;
+
+        if self.file_properties.contains(FileProperties::ATTRIBUTES) {
+            self.mapping.copy(&source_path, &destination_path)?;
+            fs::set_permissions(&destination_path, metadata.permissions())?;
+        }
+        self.apply_post_copy_actions(&destination_path)?;
+
+        let digest = match self.command.verification {
+            Some(verification) if !self.command.structure_and_size_zero_files_only => Some(verification.verify(&source_path, &destination_path)?),
+            _ => None,
+        };
+        if self.command.filter.as_ref().is_some_and(|filter| filter.handle_archive_and_reset) && !moving {
+            self.mapping.clear_archive(&source_path)?;
+        }
+
+        if moving {
+            if digest.is_none() && !same_contents(&source_path, &destination_path)? {
+                return Err(io::Error::other("The copy differs from the source, the source was not deleted."));
+            }
+            fs::remove_file(&source_path)?;
+        }
+
+        Ok(digest)
+    }
+
+    /// Adds and removes the attributes of `/a+:` and `/a-:` on a copied file
+    fn apply_post_copy_actions(&self, path: &Path) -> io::Result<()> {
+        let (add, remove) = match self.command.post_copy_actions {
+            Some(PostCopyActions::AddAttribsToFiles(add)) => (add, FileAttributes::none()),
+            Some(PostCopyActions::RmvAttribsFromFiles(remove)) => (FileAttributes::none(), remove),
+            Some(PostCopyActions::_MULTIPLE(add, remove)) => (add, remove),
+            None => return Ok(()),
+        };
+
+        self.mapping.apply(path, &add, &remove)
+    }
+
+    /// Copies up to `len` bytes of a file, throttled if there is a bandwidth limit
+    fn copy_data(&self, source: &mut File, destination: &mut File, size: u64, len: u64) -> io::Result<u64> {
+        let limiter = match &self.limiter {
+            Some(limiter) if size >= self.throttle_threshold => limiter,
+            _ => return io::copy(&mut Read::by_ref(source).take(len), destination),
+        };
+
+        let mut buffer = vec![0; self.chunk_size];
+        let mut copied = 0;
+        while copied < len {
+            let max = buffer.len().min((len - copied).min(usize::MAX as u64) as usize);
+            let read = match source.read(&mut buffer[..max]) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+
+            limiter.acquire(read as u64);
+            destination.write_all(&buffer[..read])?;
+            copied += read as u64;
+        }
+
+        Ok(copied)
+    }
+
+    /// Copies a file one chunk at a time and records the progress in a restart marker,
+    /// continuing from the marker of an interrupted copy if it is still valid.
+    /// The destination has the partial file time until the copy is complete, also after a chunk failed.
+    /// Returns the bytes copied by this call.
+    fn copy_data_restartable(&self, source: &mut File, destination: &mut File, destination_path: &Path, metadata: &fs::Metadata) -> io::Result<u64> {
+        let size = metadata.len();
+        let mut marker = RestartMarker::new(size, metadata.modified()?, 0);
+        let mut offset = match RestartMarker::read(destination_path)? {
+            Some(previous) => previous.resume_offset(source, destination, &marker)?,
+            None => 0,
+        };
+        let resumed_at = offset;
+
+        source.seek(SeekFrom::Start(offset))?;
+        destination.seek(SeekFrom::Start(offset))?;
+        while offset < size {
+            let copied = self.copy_data(source, destination, size, restart::CHUNK_SIZE.min(size - offset));
+            destination.set_modified(restart::partial_file_time())?;
+            let copied = copied?;
+            if copied == 0 {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "The source file became shorter while it was copied."));
+            }
+
+            destination.sync_data()?;
+            offset += copied;
+            marker.offset = offset;
+            marker.write(destination_path)?;
+        }
+
+        destination.set_len(size)?;
+        destination.set_modified(SystemTime::now())?;
+        RestartMarker::remove(destination_path)?;
+        Ok(size - resumed_at)
+    }
+
+    /// Whether `/timfix` fixes the timestamps of a skipped file,
+    /// which it does for files that are the same apart from their timestamps or attributes.
+    /// Special files are left alone, opening them could block.
+    fn fixes_times(&self, entry: &PlanEntry) -> bool {
+        self.command.fix_file_times && self.file_properties.contains(FileProperties::TIME_STAMPS) &&
+            matches!(entry.class, FileClass::Same | FileClass::Tweaked) && entry.source.is_some_and(|source| source.link.is_none()) &&
+            entry.source.into_iter().chain(entry.destination).all(|metadata| !metadata.special)
+    }
+
+    /// Gives a skipped file the timestamps of its source if they differ
+    fn fix_times(&self, path: &Path) -> io::Result<()> {
+        let (source, destination) = (fs::metadata(self.command.source.join(path))?, self.command.destination.join(path));
+        let current = fs::metadata(&destination)?;
+        if current.modified()? == source.modified()? && current.accessed()? == source.accessed()? {
+            return Ok(());
+        }
+
+        File::open(&destination)?.set_times(file_times(&source)?)
+    }
+
+    /// Gives the created directories the security information and attributes of their source once everything
+    /// below them was written, deepest first, so a read-only directory does not keep its own files out.
+    /// Directories that failed are left alone.
+    fn copy_dir_attributes(&self, entries: &[PlanEntry], executions: &mut [Execution]) {
+        for (entry, Execution { outcome, .. }) in entries.iter().zip(executions.iter_mut()).rev() {
+            if !entry.is_dir || entry.action != Action::Copy || outcome.is_failure() {
+                continue;
+            }
+
+            if let Err(err) = self.set_dir_attributes(&entry.path) {
+                *outcome = Outcome::Failed(err.to_string());
+            }
+        }
+    }
+
+    /// Gives the directories the timestamps of their source once everything below them was written,
+    /// deepest first. Directories that were excluded or failed are left alone.
+    fn copy_dir_times(&self, entries: &[PlanEntry], executions: &mut [Execution]) {
+        for (entry, Execution { outcome, .. }) in entries.iter().zip(executions.iter_mut()).rev() {
+            let source = match entry.source {
+                Some(source) if entry.is_dir && source.link.is_none() => source,
+                _ => continue,
+            };
+            if matches!(entry.action, Action::Purge | Action::Skip(Some(_))) || outcome.is_failure() {
+                continue;
+            }
+
+            let times = fs::FileTimes::new().set_accessed(source.accessed).set_modified(source.modified);
+            match File::open(self.command.destination.join(&entry.path)).and_then(|dir| dir.set_times(times)) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => *outcome = Outcome::Failed(err.to_string()),
+                _ => (),
+            }
+        }
+    }
+
+    /// Removes the source directories that `/move` emptied, deepest first.
+    /// Directories that still have entries, such as files that failed or were excluded, are kept,
+    /// and so are directories that were excluded or do not exist in the destination.
+    fn remove_source_dirs(&self, entries: &[PlanEntry], executions: &mut [Execution]) {
+        for (entry, Execution { outcome, .. }) in entries.iter().zip(executions.iter_mut()).rev() {
+            if !entry.is_dir || entry.source.is_none_or(|source| source.link.is_some()) || matches!(entry.action, Action::Skip(Some(_))) ||
+                    outcome.is_failure() || !self.command.destination.join(&entry.path).is_dir() {
+                continue;
+            }
+
+            match fs::remove_dir(self.command.source.join(&entry.path)) {
+                Err(err) if err.kind() != io::ErrorKind::DirectoryNotEmpty && err.kind() != io::ErrorKind::NotFound => {
+                    *outcome = Outcome::Failed(err.to_string());
+                },
+                _ => (),
+            }
+        }
+    }
+
+    /// Deletes an extra file and the restart marker it may have
+    fn purge_file(&self, path: &Path) -> io::Result<()> {
+        let destination = self.command.destination.join(path);
+        fs::remove_file(&destination)?;
+        RestartMarker::remove(&destination)
+    }
+
+    fn purge_dir(&self, path: &Path) -> io::Result<()> {
+        fs::remove_dir_all(self.command.destination.join(path))
+    }
+}
+
+/// Whether the source of a move is gone and the destination has the size and modification time
+/// it was planned with, which is what an attempt that renamed it before failing leaves behind
+fn already_moved(entry: &PlanEntry, source: &Path, destination: &Path) -> io::Result<bool> {
+    match fs::symlink_metadata(source) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+        result => return result.map(|_| false),
+    }
+
+    let (planned, destination) = match (entry.source, fs::metadata(destination)) {
+        (Some(planned), Ok(destination)) => (planned, destination),
+        _ => return Ok(false),
+    };
+    Ok(destination.len() == planned.size && destination.modified()? == planned.modified)
+}
+
+/// Renames a file if the source and destination are on the same file system.
+/// Returns whether it was renamed.
+fn rename(source: &Path, destination: &Path) -> io::Result<bool> {
+    match fs::rename(source, destination) {
+        Ok(()) => Ok(true),
+        Err(err) if err.kind() == io::ErrorKind::CrossesDevices => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// The access and modification times of a file, to the nanosecond where the platform records them
+fn file_times(metadata: &fs::Metadata) -> io::Result<fs::FileTimes> {
+    Ok(fs::FileTimes::new().set_accessed(metadata.accessed()?).set_modified(metadata.modified()?))
+}
+
+/// Recreates a symbolic link with the same target, replacing the destination if it exists
+fn copy_link(source: &Path, destination: &Path) -> io::Result<()> {
+    let target = fs::read_link(source)?;
+    match fs::remove_file(destination) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+        _ => (),
+    }
+
+    #[cfg(unix)]
+    return std::os::unix::fs::symlink(target, destination);
+    #[cfg(not(unix))]
+    return Err(io::Error::new(io::ErrorKind::Unsupported, format!("Cannot create a link to {}.", target.display())));
+}
+
+/// Whether two files have the same length and contents
+fn same_contents(a: &Path, b: &Path) -> io::Result<bool> {
+    let (mut a, mut b) = (File::open(a)?, File::open(b)?);
+    if a.metadata()?.len() != b.metadata()?.len() {
+        return Ok(false);
+    }
+
+    let (mut buffer_a, mut buffer_b) = (vec![0; 64 * 1024], vec![0; 64 * 1024]);
+    loop {
+        let read = a.read(&mut buffer_a)?;
+        if read == 0 {
+            return Ok(true);
+        }
+        b.read_exact(&mut buffer_b[..read])?;
+        if buffer_a[..read] != buffer_b[..read] {
+            return Ok(false);
+        }
+    }
+}
+
+/// Whether an error may go away by itself, so the operation is worth retrying:
+/// interruptions, timeouts, busy files and network errors. Everything else fails at once.
+/// A running executable cannot be written to, which is the closest to a sharing violation on Windows.
+fn is_transient(err: &io::Error) -> bool {
+    matches!(err.kind(),
+        io::ErrorKind::Interrupted | io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock |
+        io::ErrorKind::ResourceBusy | io::ErrorKind::ExecutableFileBusy |
+        io::ErrorKind::ConnectionRefused | io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionAborted |
+        io::ErrorKind::NotConnected | io::ErrorKind::BrokenPipe | io::ErrorKind::NetworkDown |
+        io::ErrorKind::NetworkUnreachable | io::ErrorKind::HostUnreachable | io::ErrorKind::StaleNetworkFileHandle)
+}
+
+/// Opens a file for writing, creating it if needed and truncating it if asked to.
+/// The read-only attribute of an existing file is cleared 
+/// like robocopy does when it overwrites one.
+fn open_file(path: &Path, truncate: bool) -> io::Result<File> {
+    let open = || fs::OpenOptions::new().read(true).write(true).create(true).truncate(truncate).open(path);
+
+    match open() {
+        Err(err) if err.kind() == io::ErrorKind::PermissionDenied => {
+            // unless the file is read-only, something else keeps it from being opened and its error is the one to report
+            let mut permissions = match fs::metadata(path) {
+                Ok(metadata) if metadata.permissions().readonly() => metadata.permissions(),
+                _ => return Err(err),
+            };
+
+            #[allow(clippy::permissions_set_readonly_false)]
+            permissions.set_readonly(false);
+            fs::set_permissions(path, permissions)?;
+            open()
+        },
+        result => result,
+    }
+}
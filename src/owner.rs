@@ -0,0 +1,249 @@
+//! Ownership on Linux
+//!
+//! Robocopy copies the owner of a file with `/copy:O`. Native execution copies
+//! the user and group ids, translated through an [`OwnerMapping`] for copies
+//! between machines whose users and groups have different ids.
+//! Changing the owner needs privileges the process may not have, an ownership
+//! the process is not permitted to set is reported and does not fail the copy.
+//! A run maps owners through an [`OwnerLookup`], which looks up each name and id once.
+
+use std::{collections::HashMap, fmt, io, path::Path, sync::{Mutex, MutexGuard}};
+
+/// A user or group, by name or by id
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Principal {
+    Id(u32),
+    Name(String),
+}
+
+impl fmt::Display for Principal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Id(id) => write!(f, "{}", id),
+            Self::Name(name) => f.write_str(name),
+        }
+    }
+}
+
+impl From<u32> for Principal {
+    fn from(id: u32) -> Self {
+        Self::Id(id)
+    }
+}
+
+impl From<&str> for Principal {
+    /// A number is an id, anything else a name
+    fn from(principal: &str) -> Self {
+        principal.parse().map(Self::Id).unwrap_or_else(|_| Self::Name(String::from(principal)))
+    }
+}
+
+/// Users and groups that are replaced when ownership is copied.
+/// Names of the source are the names on this machine, as are the names they are replaced with.
+/// Owners that are not in the table keep their ids.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OwnerMapping {
+    pub users: Vec<(Principal, Principal)>,
+    pub groups: Vec<(Principal, Principal)>,
+}
+
+/// Whether a principal is a user or a group
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Kind {
+    User,
+    Group,
+}
+
+impl OwnerMapping {
+    /// Maps a user of the source to the user id of the destination
+    pub fn user(&self, uid: u32) -> io::Result<u32> {
+        self.translate(&self.users, uid, Kind::User, &Lookups::default())
+    }
+
+    /// Maps a group of the source to the group id of the destination
+    pub fn group(&self, gid: u32) -> io::Result<u32> {
+        self.translate(&self.groups, gid, Kind::Group, &Lookups::default())
+    }
+
+    /// Gives the destination the owner and group of the source, mapped through the table.
+    /// Returns the error if the process is not permitted to, other errors fail.
+    pub fn copy(&self, source: &Path, destination: &Path) -> io::Result<Option<io::Error>> {
+        self.copy_with(source, destination, &Lookups::default())
+    }
+
+    fn translate(&self, table: &[(Principal, Principal)], id: u32, kind: Kind, lookups: &Lookups) -> io::Result<u32> {
+        if table.is_empty() {
+            return Ok(id);
+        }
+
+        let name = lookups.name_of(id, kind)?;
+        let target = table.iter().find(|(from, _)| match from {
+            Principal::Id(from) => *from == id,
+            Principal::Name(from) => name.as_ref() == Some(from),
+        });
+
+        match target {
+            None => Ok(id),
+            Some((_, Principal::Id(to))) => Ok(*to),
+            Some((_, Principal::Name(to))) => lookups.id_of(to, kind)?.ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, format!("There is no {} {}.", if kind == Kind::User { "user" } else { "group" }, to))
+            }),
+        }
+    }
+
+    #[cfg(unix)]
+    fn copy_with(&self, source: &Path, destination: &Path, lookups: &Lookups) -> io::Result<Option<io::Error>> {
+        use std::os::unix::fs::MetadataExt;
+
+        let metadata = std::fs::metadata(source)?;
+        let uid = self.translate(&self.users, metadata.uid(), Kind::User, lookups)?;
+        let gid = self.translate(&self.groups, metadata.gid(), Kind::Group, lookups)?;
+
+        match std::os::unix::fs::chown(destination, Some(uid), Some(gid)) {
+            Ok(()) => Ok(None),
+            Err(err) if err.kind() == io::ErrorKind::PermissionDenied => Ok(Some(err)),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Files have no unix owner to copy
+    #[cfg(not(unix))]
+    fn copy_with(&self, _source: &Path, _destination: &Path, _lookups: &Lookups) -> io::Result<Option<io::Error>> {
+        Ok(Some(io::Error::new(io::ErrorKind::Unsupported, "Ownership is not supported on this platform.")))
+    }
+}
+
+/// An [`OwnerMapping`] for a run, which looks up the name of each id and the id of each name once
+#[derive(Debug, Default)]
+pub struct OwnerLookup {
+    mapping: OwnerMapping,
+    lookups: Lookups,
+}
+
+impl OwnerLookup {
+    pub fn new(mapping: OwnerMapping) -> Self {
+        Self { mapping, lookups: Lookups::default() }
+    }
+
+    /// Maps a user of the source to the user id of the destination
+    pub fn user(&self, uid: u32) -> io::Result<u32> {
+        self.mapping.translate(&self.mapping.users, uid, Kind::User, &self.lookups)
+    }
+
+    /// Maps a group of the source to the group id of the destination
+    pub fn group(&self, gid: u32) -> io::Result<u32> {
+        self.mapping.translate(&self.mapping.groups, gid, Kind::Group, &self.lookups)
+    }
+
+    /// Gives the destination the owner and group of the source, see [`OwnerMapping::copy`]
+    pub fn copy(&self, source: &Path, destination: &Path) -> io::Result<Option<io::Error>> {
+        self.mapping.copy_with(source, destination, &self.lookups)
+    }
+}
+
+/// Names and ids looked up in the user and group databases so far
+#[derive(Debug, Default)]
+struct Lookups {
+    names: Mutex<HashMap<(Kind, u32), Option<String>>>,
+    ids: Mutex<HashMap<(Kind, String), Option<u32>>>,
+}
+
+impl Lookups {
+    fn name_of(&self, id: u32, kind: Kind) -> io::Result<Option<String>> {
+        if let Some(name) = lock(&self.names).get(&(kind, id)) {
+            return Ok(name.clone());
+        }
+
+        let name = name_of(id, kind)?;
+        lock(&self.names).insert((kind, id), name.clone());
+        Ok(name)
+    }
+
+    fn id_of(&self, name: &str, kind: Kind) -> io::Result<Option<u32>> {
+        if let Some(id) = lock(&self.ids).get(&(kind, String::from(name))) {
+            return Ok(*id);
+        }
+
+        let id = id_of(name, kind)?;
+        lock(&self.ids).insert((kind, String::from(name)), id);
+        Ok(id)
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// The name of a user or group id, none if it has no name
+#[cfg(unix)]
+fn name_of(id: u32, kind: Kind) -> io::Result<Option<String>> {
+    use std::{ffi::CStr, mem::MaybeUninit, ptr};
+
+    lookup(|buffer| match kind {
+        Kind::User => {
+            let (mut entry, mut result) = (MaybeUninit::<libc::passwd>::uninit(), ptr::null_mut());
+            // SAFETY: the buffer outlives the entry and its length is passed along
+            let code = unsafe { libc::getpwuid_r(id, entry.as_mut_ptr(), buffer.as_mut_ptr(), buffer.len(), &mut result) };
+            // SAFETY: a result that is not null points to the entry, whose name is initialized
+            (code, (!result.is_null()).then(|| unsafe { CStr::from_ptr((*result).pw_name) }.to_string_lossy().into_owned()))
+        },
+        Kind::Group => {
+            let (mut entry, mut result) = (MaybeUninit::<libc::group>::uninit(), ptr::null_mut());
+            // SAFETY: the buffer outlives the entry and its length is passed along
+            let code = unsafe { libc::getgrgid_r(id, entry.as_mut_ptr(), buffer.as_mut_ptr(), buffer.len(), &mut result) };
+            // SAFETY: a result that is not null points to the entry, whose name is initialized
+            (code, (!result.is_null()).then(|| unsafe { CStr::from_ptr((*result).gr_name) }.to_string_lossy().into_owned()))
+        },
+    })
+}
+
+/// The id of a user or group name, none if there is no such user or group
+#[cfg(unix)]
+fn id_of(name: &str, kind: Kind) -> io::Result<Option<u32>> {
+    use std::{ffi::CString, mem::MaybeUninit, ptr};
+
+    let name = CString::new(name).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    lookup(|buffer| match kind {
+        Kind::User => {
+            let (mut entry, mut result) = (MaybeUninit::<libc::passwd>::uninit(), ptr::null_mut());
+            // SAFETY: the buffer outlives the entry and its length is passed along
+            let code = unsafe { libc::getpwnam_r(name.as_ptr(), entry.as_mut_ptr(), buffer.as_mut_ptr(), buffer.len(), &mut result) };
+            // SAFETY: a result that is not null points to the initialized entry
+            (code, (!result.is_null()).then(|| unsafe { (*result).pw_uid }))
+        },
+        Kind::Group => {
+            let (mut entry, mut result) = (MaybeUninit::<libc::group>::uninit(), ptr::null_mut());
+            // SAFETY: the buffer outlives the entry and its length is passed along
+            let code = unsafe { libc::getgrnam_r(name.as_ptr(), entry.as_mut_ptr(), buffer.as_mut_ptr(), buffer.len(), &mut result) };
+            // SAFETY: a result that is not null points to the initialized entry
+            (code, (!result.is_null()).then(|| unsafe { (*result).gr_gid }))
+        },
+    })
+}
+
+/// Runs a `get*_r` lookup with a buffer that grows while it is too small for the entry.
+/// The lookup returns its code and what it read from the entry, none if there was none.
+#[cfg(unix)]
+fn lookup<T>(mut lookup: impl FnMut(&mut [libc::c_char]) -> (libc::c_int, Option<T>)) -> io::Result<Option<T>> {
+    const MAX_BUFFER: usize = 1 << 20;
+
+    let mut buffer = vec![0 as libc::c_char; 16 * 1024];
+    loop {
+        match lookup(&mut buffer) {
+            (0, found) => return Ok(found),
+            (libc::ERANGE, _) if buffer.len() < MAX_BUFFER => buffer.resize(buffer.len() * 2, 0),
+            (libc::ENOENT, _) | (libc::ESRCH, _) | (libc::EBADF, _) | (libc::EPERM, _) => return Ok(None),
+            (code, _) => return Err(io::Error::from_raw_os_error(code)),
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn name_of(_id: u32, _kind: Kind) -> io::Result<Option<String>> {
+    Ok(None)
+}
+
+#[cfg(not(unix))]
+fn id_of(_name: &str, _kind: Kind) -> io::Result<Option<u32>> {
+    Ok(None)
+}
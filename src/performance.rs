@@ -1,24 +1,60 @@
 //! Performance options
 
-use std::{convert::TryInto, ffi::OsString, ops::Add};
+use std::{convert::TryInto, ffi::OsString, ops::Add, time::Duration};
 
 use crate::MultipleVariant;
+use crate::size::ByteSize;
 
 /// Only one Performance choice can be chosen
 #[allow(non_camel_case_types)]
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum PerformanceChoice {
     Threads(u8), // max 128
-    InterPacketGap(usize), // todo max
+    InterPacketGap(usize), // milliseconds between blocks of INTER_PACKET_GAP_BLOCK bytes
+    Bandwidth(u64), // bytes per second, sent to robocopy as the equivalent inter packet gap
     Default, // Threads thread, how many (case None = default) or how big the gap
             // when adding this variant implies usage of the other variant
 }
 
 impl PerformanceChoice {
+    /// Thread count robocopy uses for `/MT` without a number
+    pub const DEFAULT_THREADS: u8 = 8;
+    /// Highest thread count robocopy accepts
+    pub const MAX_THREADS: u8 = 128;
+
+    /// The number of threads to copy with,
+    /// `Threads(0)` uses the robocopy default and other choices copy on a single thread.
+    pub fn thread_count(&self) -> usize {
+        match self {
+            Self::Threads(0) => Self::DEFAULT_THREADS as usize,
+            Self::Threads(threads) => (*threads).min(Self::MAX_THREADS) as usize,
+            _ => 1,
+        }
+    }
+
+    /// The number of bytes robocopy sends between two inter packet gaps
+    pub const INTER_PACKET_GAP_BLOCK: u64 = 64 * 1024;
+
+    /// The inter packet gap in milliseconds that limits the bandwidth 
+    /// to about `bytes_per_sec`, ignoring the time the transfer itself takes.
+    pub fn inter_packet_gap_for(bytes_per_sec: u64) -> usize {
+        (Self::INTER_PACKET_GAP_BLOCK * 1000).div_ceil(bytes_per_sec.max(1)) as usize
+    }
+
+    /// The bandwidth in bytes per second the choice limits copying to, if any
+    pub fn bandwidth(&self) -> Option<u64> {
+        match self {
+            Self::InterPacketGap(gap) if *gap > 0 => Some(Self::INTER_PACKET_GAP_BLOCK * 1000 / *gap as u64),
+            Self::Bandwidth(bytes_per_sec) if *bytes_per_sec > 0 => Some(*bytes_per_sec),
+            _ => None
+        }
+    }
+
     fn as_os_string(&self) -> Option<OsString> {
         match self {
             Self::Threads(threads) => Some(OsString::from(format!("/MT:{}", threads))),
             Self::InterPacketGap(gap) => Some(OsString::from(format!("/ipg:{}", gap))),
+            Self::Bandwidth(bytes_per_sec) if *bytes_per_sec > 0 => Some(OsString::from(format!("/ipg:{}", Self::inter_packet_gap_for(*bytes_per_sec)))),
             _ => None
         }
     }
@@ -126,7 +162,7 @@ impl MultipleVariant for PerformanceOptions {
                     Self::COPY_RATHER_THAN_FOLLOW_LINK(*choice),
                 ];
 
-                variants.iter().zip(filters.iter()).filter(|(_, exists)| **exists).into_iter().unzip::<&Self, &bool, Vec<Self>, Vec<bool>>().0
+                variants.iter().zip(filters.iter()).filter(|(_, exists)| **exists).unzip::<&Self, &bool, Vec<Self>, Vec<bool>>().0
             },
             attrib => vec![*attrib],
         }
@@ -143,6 +179,16 @@ impl PerformanceOptions {
         }
     }
 
+    /// The number of threads to copy with, see [`PerformanceChoice::thread_count`]
+    pub fn thread_count(&self) -> usize {
+        self.performance_choice().thread_count()
+    }
+
+    /// Whether symbolic links are copied as links rather than followed (`/sl`)
+    pub fn copies_links(&self) -> bool {
+        self.single_variants().iter().any(|option| option.index_of() == Some(2))
+    }
+
     pub fn performance_choice(&self) -> PerformanceChoice {
         match self {
             Self::PerformanceChoiceOnly(choice) | 
@@ -164,6 +210,23 @@ pub struct RetrySettings {
     pub await_share_names_def: bool,
 }
 
+impl RetrySettings {
+    /// Retries robocopy makes when `/r` is not given, as set in the registry by default
+    pub const DEFAULT_RETRIES: usize = 1_000_000;
+    /// Seconds robocopy waits between retries when `/w` is not given, as set in the registry by default
+    pub const DEFAULT_WAIT: usize = 30;
+
+    /// The number of retries after a failed copy
+    pub fn retries(&self) -> usize {
+        self.specify_retries_failed_copies.unwrap_or(Self::DEFAULT_RETRIES)
+    }
+
+    /// The time to wait between retries
+    pub fn wait(&self) -> Duration {
+        Duration::from_secs(self.specify_wait_between_retries.unwrap_or(Self::DEFAULT_WAIT) as u64)
+    }
+}
+
 impl From<&RetrySettings> for Vec<OsString> {
     fn from(rs: &RetrySettings) -> Self {
         let mut result = Vec::new();
@@ -189,3 +252,47 @@ impl From<RetrySettings> for Vec<OsString> {
         (&rs).into()
     }
 }
+
+/// I/O throttling and low free space options
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IoSettings {
+    /// Requested maximum size of each read or write
+    pub max_io_size: Option<ByteSize>,
+    /// Requested I/O rate in bytes per second
+    pub io_rate: Option<ByteSize>,
+    /// Files smaller than this are not throttled
+    pub throttle_threshold: Option<ByteSize>,
+    
+    /// Pause copying when the destination runs low on free space
+    pub low_free_space_mode: bool,
+    /// Free space to keep on the destination, implies low_free_space_mode
+    pub low_free_space_floor: Option<ByteSize>,
+}
+
+impl From<&IoSettings> for Vec<OsString> {
+    fn from(ios: &IoSettings) -> Self {
+        let mut result = Vec::new();
+
+        if let Some(size) = ios.max_io_size {
+            result.push(OsString::from(format!("/iomaxsize:{}", size)))
+        }
+        if let Some(rate) = ios.io_rate {
+            result.push(OsString::from(format!("/iorate:{}", rate)))
+        }
+        if let Some(threshold) = ios.throttle_threshold {
+            result.push(OsString::from(format!("/threshold:{}", threshold)))
+        }
+        if let Some(floor) = ios.low_free_space_floor {
+            result.push(OsString::from(format!("/lfsm:{}", floor)))
+        } else if ios.low_free_space_mode {
+            result.push(OsString::from("/lfsm"))
+        }
+
+        result
+    }
+}
+impl From<IoSettings> for Vec<OsString> {
+    fn from(ios: IoSettings) -> Self {
+        (&ios).into()
+    }
+}
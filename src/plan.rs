@@ -0,0 +1,444 @@
+//! Dry-run planning
+//!
+//! Walks the source and destination trees and works out what a command
+//! would do with each file and directory, without changing anything.
+
+use std::{collections::{BTreeMap, HashMap}, ffi::OsString, fs, io, path::{Path, PathBuf}};
+
+use crate::{pool, RobocopyCommand};
+use crate::attributes::{AttributeMapping, AttributeProvider, Permissions};
+use crate::classify::has_variant;
+use crate::evaluate::{Decision, EntryMetadata, Evaluator, FileClass, Link, Rule};
+use crate::filter::FileExclusionFilterException;
+use crate::restart::RestartMarker;
+use crate::summary::Summary;
+
+/// What happens to a file or directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Copy the file or create the directory
+    Copy,
+    /// Delete the extra file or directory from the destination
+    Purge,
+    /// Leave it alone, with the rule that excluded it if there is one
+    Skip(Option<Rule>),
+}
+
+/// A file or directory of the plan
+#[derive(Debug, Clone)]
+pub struct PlanEntry {
+    /// Path relative to the source and destination roots
+    pub path: PathBuf,
+    pub is_dir: bool,
+    pub class: FileClass,
+    pub source: Option<EntryMetadata>,
+    pub destination: Option<EntryMetadata>,
+    pub action: Action,
+    /// Why the directory could not be read, its contents are not planned
+    /// and native execution fails it
+    pub error: Option<String>,
+}
+
+impl PlanEntry {
+    /// Size of the source file, or of the destination file for extras
+    pub fn size(&self) -> u64 {
+        self.source.or(self.destination).map(|metadata| metadata.size).unwrap_or(0)
+    }
+
+    /// Adds the entry to the totals of a summary
+    pub fn tally(&self, summary: &mut Summary) {
+        let size = self.size();
+        let (row, bytes) = if self.is_dir {
+            (&mut summary.dirs, None)
+        } else {
+            (&mut summary.files, Some(&mut summary.bytes))
+        };
+
+        match (&self.class, &self.action) {
+            (_, Action::Skip(Some(Rule::Files))) | (_, Action::Skip(Some(Rule::ExcludeExtra))) => (),
+            (FileClass::Extra, _) => {
+                row.extras += 1;
+                if let Some(bytes) = bytes {
+                    bytes.extras += size;
+                }
+            },
+            (class, action) => {
+                row.total += 1;
+                match (class, action) {
+                    (FileClass::Mismatched, _) => row.mismatch += 1,
+                    (_, Action::Copy) => row.copied += 1,
+                    _ => row.skipped += 1,
+                }
+
+                if let Some(bytes) = bytes {
+                    bytes.total += size;
+                    match (class, action) {
+                        (FileClass::Mismatched, _) => bytes.mismatch += size,
+                        (_, Action::Copy) => bytes.copied += size,
+                        _ => bytes.skipped += size,
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Everything a command would do, in the order robocopy would do it
+#[derive(Debug, Clone, Default)]
+pub struct CopyPlan {
+    pub entries: Vec<PlanEntry>,
+    pub summary: Summary,
+}
+
+impl CopyPlan {
+    /// Plans a command by walking its source and destination.
+    /// With more than one thread the directories are read in parallel.
+    pub fn new(command: &RobocopyCommand<'_>) -> io::Result<Self> {
+        let source_root = fs::metadata(command.source)?;
+        if !source_root.is_dir() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "The source is not a directory."));
+        }
+
+        let mut planner = Planner {
+            command,
+            evaluator: Evaluator::from(command),
+            entries: Vec::new(),
+            listings: HashMap::new(),
+        };
+
+        let mapping = command.attribute_mapping.unwrap_or_default();
+        let provider = attribute_provider(command, &mapping);
+        let root = EntryMetadata {
+            attributes: provider.attributes(command.source, &source_root)?,
+            ..EntryMetadata::from(&source_root)
+        };
+        let destination_root = read_metadata(command.destination, provider, false)?;
+        planner.entries.push(PlanEntry {
+            path: PathBuf::new(),
+            is_dir: true,
+            class: planner.evaluator.classify(Some(&root), destination_root.as_ref()).unwrap(),
+            source: Some(root),
+            destination: destination_root,
+            action: if destination_root.is_some() { Action::Skip(None) } else { Action::Copy },
+            error: None,
+        });
+        let threads = command.performance_options.map(|options| options.thread_count()).unwrap_or(1);
+        if threads > 1 {
+            planner.prefetch(threads, destination_root.is_some());
+        }
+        if let Err(err) = planner.walk(Path::new(""), true, destination_root.is_some()) {
+            planner.entries[0].error = Some(err.to_string());
+        }
+
+        let mut summary = Summary::default();
+        planner.entries.iter().for_each(|entry| entry.tally(&mut summary));
+
+        Ok(Self {
+            entries: planner.entries,
+            summary,
+        })
+    }
+
+    /// The files and directories that would be copied or created
+    pub fn copies(&self) -> impl Iterator<Item = &PlanEntry> {
+        self.entries.iter().filter(|entry| entry.action == Action::Copy)
+    }
+
+    /// The files and directories that would be deleted from the destination
+    pub fn purges(&self) -> impl Iterator<Item = &PlanEntry> {
+        self.entries.iter().filter(|entry| entry.action == Action::Purge)
+    }
+
+    /// The symbolic links that are left alone because they are excluded, broken or lead into a cycle
+    pub fn skipped_links(&self) -> impl Iterator<Item = &PlanEntry> {
+        self.entries.iter().filter(|entry| matches!(&entry.action, Action::Skip(Some(rule)) if rule.is_link_rule()))
+    }
+}
+
+struct Planner<'c, 'a> {
+    command: &'c RobocopyCommand<'a>,
+    evaluator: Evaluator,
+    entries: Vec<PlanEntry>,
+    /// Directories read ahead of the walk
+    listings: HashMap<PathBuf, io::Result<Listing>>,
+}
+
+/// The entries of a directory on both sides
+#[derive(Debug, Default)]
+struct Listing {
+    sources: BTreeMap<OsString, EntryMetadata>,
+    destinations: BTreeMap<OsString, EntryMetadata>,
+}
+
+impl Listing {
+    fn read(command: &RobocopyCommand<'_>, path: &Path, in_source: bool, in_destination: bool) -> io::Result<Self> {
+        let mapping = command.attribute_mapping.unwrap_or_default();
+        let provider = attribute_provider(command, &mapping);
+        let copy_links = command.performance_options.is_some_and(|options| options.copies_links());
+
+        Ok(Self {
+            sources: if in_source { read_dir(&command.source.join(path), provider, copy_links)? } else { BTreeMap::new() },
+            destinations: if in_destination {
+                let mut destinations = read_dir(&command.destination.join(path), provider, copy_links)?;
+                // markers are hidden next to their file, a marker whose file is gone is an extra like any other file
+                let markers: Vec<OsString> = destinations.keys()
+                    .flat_map(|name| RestartMarker::names_for(name))
+                    .filter(|marker| destinations.contains_key(marker))
+                    .collect();
+                for marker in markers {
+                    destinations.remove(&marker);
+                }
+                destinations
+            } else {
+                BTreeMap::new()
+            },
+        })
+    }
+
+    /// The names of the directories and of the files, 
+    /// names that are a file on either side count as files
+    fn names(&self) -> (Vec<&OsString>, Vec<&OsString>) {
+        let mut names: Vec<&OsString> = self.sources.keys().chain(self.destinations.keys()).collect();
+        names.sort();
+        names.dedup();
+
+        names.into_iter().partition(|name| {
+            self.sources.get(*name).is_none_or(|source| source.is_dir) && 
+                self.destinations.get(*name).is_none_or(|destination| destination.is_dir)
+        })
+    }
+}
+
+impl<'c, 'a> Planner<'c, 'a> {
+    /// Reads the directories the walk will descend into ahead of it,
+    /// level by level with one directory per thread at a time.
+    fn prefetch(&mut self, threads: usize, in_destination: bool) {
+        let mut level = vec![(PathBuf::new(), true, in_destination)];
+
+        while !level.is_empty() {
+            let command = self.command;
+            let listings = pool::map(&level, threads, |(path, in_source, in_destination)| Listing::read(command, path, *in_source, *in_destination));
+
+            let mut next = Vec::new();
+            for ((path, _, _), listing) in level.into_iter().zip(listings) {
+                if let Ok(listing) = &listing {
+                    next.append(&mut self.subdirectories(&path, listing));
+                }
+                self.listings.insert(path, listing);
+            }
+            level = next;
+        }
+    }
+
+    /// The listing of a directory, read ahead or read now
+    fn listing(&mut self, path: &Path, in_source: bool, in_destination: bool) -> io::Result<Listing> {
+        self.listings.remove(path).unwrap_or_else(|| Listing::read(self.command, path, in_source, in_destination))
+    }
+
+    /// The subdirectories of a listing the walk descends into 
+    /// and on which sides they exist
+    fn subdirectories(&self, path: &Path, listing: &Listing) -> Vec<(PathBuf, bool, bool)> {
+        listing.names().0.into_iter().filter_map(|name| {
+            let source = listing.sources.get(name);
+            let destination = listing.destinations.get(name);
+            let path = path.join(name);
+
+            let (class, action) = self.decide_directory(&path, source, destination);
+            match action {
+                Action::Purge => Some((path, false, true)),
+                _ if Self::descends(class, &action) => Some((path, source.is_some(), destination.is_some())),
+                _ => None,
+            }
+        }).collect()
+    }
+
+    /// Whether the walk descends into a directory that is not purged
+    fn descends(class: FileClass, action: &Action) -> bool {
+        !matches!(action, Action::Skip(Some(_))) && class != FileClass::Extra
+    }
+
+    /// Plans the contents of a directory, files first and then the subdirectories.
+    /// Returns whether anything was planned to be copied below it, or the error reading the directory.
+    /// Subdirectories that cannot be read get their error and the walk goes on.
+    fn walk(&mut self, path: &Path, in_source: bool, in_destination: bool) -> io::Result<bool> {
+        let listing = self.listing(path, in_source, in_destination)?;
+        let (dirs, files) = listing.names();
+
+        let mut copies = false;
+        for name in files {
+            let source = listing.sources.get(name).copied();
+            let destination = listing.destinations.get(name).copied();
+            let path = path.join(name);
+
+            let (class, action) = match self.evaluator.classify(source.as_ref(), destination.as_ref()).unwrap() {
+                FileClass::Mismatched => (FileClass::Mismatched, Action::Skip(None)),
+                _ => self.decide(self.evaluator.evaluate_file(&path, source.as_ref(), destination.as_ref()), source.as_ref(), destination.as_ref()),
+            };
+            copies |= action == Action::Copy;
+
+            self.entries.push(PlanEntry { path, is_dir: false, class, source, destination, action, error: None });
+        }
+
+        for name in dirs {
+            let source = listing.sources.get(name).copied();
+            let destination = listing.destinations.get(name).copied();
+            let path = path.join(name);
+
+            let (class, action) = self.decide_directory(&path, source.as_ref(), destination.as_ref());
+
+            let index = self.entries.len();
+            self.entries.push(PlanEntry { path: path.clone(), is_dir: true, class, source, destination, action: action.clone(), error: None });
+
+            if action == Action::Purge {
+                if let Err(err) = self.purge(&path) {
+                    self.entries[index].error = Some(err.to_string());
+                }
+            } else if Self::descends(class, &action) {
+                let below = match self.walk(&path, source.is_some(), destination.is_some()) {
+                    Ok(below) => below,
+                    Err(err) => {
+                        self.entries[index].error = Some(err.to_string());
+                        continue;
+                    },
+                };
+                if class == FileClass::Lonely && !below && !self.command.empty_dir_copy {
+                    self.entries[index].action = Action::Skip(None);
+                }
+                copies |= below || self.entries[index].action == Action::Copy;
+            }
+        }
+
+        Ok(copies)
+    }
+
+    /// Marks everything below an extra directory as purged,
+    /// or returns the error reading it
+    fn purge(&mut self, path: &Path) -> io::Result<()> {
+        let destinations = self.listing(path, false, true)?.destinations;
+        let (dirs, files): (Vec<_>, Vec<_>) = destinations.into_iter().partition(|(_, metadata)| metadata.is_dir);
+
+        for (name, metadata) in files.into_iter().chain(dirs) {
+            let path = path.join(name);
+            self.entries.push(PlanEntry {
+                path: path.clone(),
+                is_dir: metadata.is_dir,
+                class: FileClass::Extra,
+                source: None,
+                destination: Some(metadata),
+                action: Action::Purge,
+                error: None,
+            });
+
+            let index = self.entries.len() - 1;
+            if metadata.is_dir && metadata.link.is_none() {
+                if let Err(err) = self.purge(&path) {
+                    self.entries[index].error = Some(err.to_string());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Decides on a directory, excluding links to directories the walk is already in
+    fn decide_directory(&self, path: &Path, source: Option<&EntryMetadata>, destination: Option<&EntryMetadata>) -> (FileClass, Action) {
+        let mut decision = self.evaluator.evaluate_directory(path, source, destination);
+        if decision.is_included() && source.is_some_and(|source| source.link == Some(Link::Directory)) && self.is_cycle(path) {
+            decision = Decision::Exclude(Rule::LinkCycle);
+        }
+
+        self.decide(decision, source, destination)
+    }
+
+    /// Whether a followed directory link leads to the source root, one of the directories
+    /// the walk passed through to reach it or a parent of them
+    fn is_cycle(&self, path: &Path) -> bool {
+        let target = match fs::canonicalize(self.command.source.join(path)) {
+            Ok(target) => target,
+            Err(_) => return false,
+        };
+
+        path.ancestors().skip(1).any(|ancestor| {
+            fs::canonicalize(self.command.source.join(ancestor)).is_ok_and(|ancestor| ancestor.starts_with(&target))
+        })
+    }
+
+    fn decide(&self, decision: Decision, source: Option<&EntryMetadata>, destination: Option<&EntryMetadata>) -> (FileClass, Action) {
+        let class = self.evaluator.classify(source, destination).unwrap();
+
+        let action = match decision {
+            Decision::Include(FileClass::Extra) if self.command.remove_files_and_dirs_not_in_src => Action::Purge,
+            Decision::Include(FileClass::Extra) | Decision::Include(FileClass::Mismatched) => Action::Skip(None),
+            Decision::Include(FileClass::Same) if class == FileClass::Same && source.is_some_and(|source| source.is_dir) => Action::Skip(None),
+            Decision::Include(_) => Action::Copy,
+            Decision::Exclude(rule) => Action::Skip(Some(rule)),
+        };
+
+        (class, action)
+    }
+}
+
+/// Where a plan reads attributes from: the provider of the command, or else the mapping
+/// if `/a`, `/m`, `/ia`, `/xa` or `/it` need more than the [`Permissions`]
+fn attribute_provider<'p>(command: &'p RobocopyCommand<'_>, mapping: &'p AttributeMapping) -> &'p dyn AttributeProvider {
+    if let Some(provider) = command.attribute_provider.as_deref() {
+        return provider;
+    }
+
+    let needs_attributes = command.filter.as_ref().is_some_and(|filter| {
+        filter.archive_only || filter.handle_archive_and_reset || filter.include_only_files_with_any_of_these_attribs.is_some()
+            || filter.file_exclusion_filter.as_ref().and_then(Evaluator::excluded_attributes).is_some()
+            || has_variant(filter.file_exclusion_filter_exceptions.as_ref(), &FileExclusionFilterException::TWEAKED)
+    });
+    if needs_attributes { mapping } else { &Permissions }
+}
+
+/// Metadata of a path with the attributes of the provider, or none if it does not exist.
+/// Symbolic links are followed unless they are copied as links or broken,
+/// then the metadata is the one of the link itself.
+fn read_metadata(path: &Path, provider: &dyn AttributeProvider, copy_links: bool) -> io::Result<Option<EntryMetadata>> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    if !metadata.file_type().is_symlink() {
+        return Ok(Some(EntryMetadata {
+            attributes: provider.attributes(path, &metadata)?,
+            ..EntryMetadata::from(&metadata)
+        }));
+    }
+
+    let (link, target) = match fs::metadata(path) {
+        Ok(target) if target.is_dir() => (Link::Directory, Some(target)),
+        Ok(target) => (Link::File, Some(target)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => (Link::Broken, None),
+        Err(err) => return Err(err),
+    };
+
+    Ok(Some(match target {
+        Some(target) if !copy_links => EntryMetadata {
+            attributes: provider.attributes(path, &target)?,
+            link: Some(link),
+            ..EntryMetadata::from(&target)
+        },
+        _ => EntryMetadata {
+            link: Some(link),
+            ..EntryMetadata::from(&metadata)
+        },
+    }))
+}
+
+/// Metadata of the entries of a directory sorted by name
+fn read_dir(path: &Path, provider: &dyn AttributeProvider, copy_links: bool) -> io::Result<BTreeMap<OsString, EntryMetadata>> {
+    let mut entries = BTreeMap::new();
+
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        if let Some(metadata) = read_metadata(&entry.path(), provider, copy_links)? {
+            entries.insert(entry.file_name(), metadata);
+        }
+    }
+
+    Ok(entries)
+}
@@ -0,0 +1,34 @@
+//! A minimal worker pool for the native engine
+
+use std::{panic, sync::atomic::{AtomicUsize, Ordering}, thread};
+
+/// Maps the items on up to `threads` threads, 
+/// the results are in the order of the items.
+pub(crate) fn map<T: Sync, R: Send>(items: &[T], threads: usize, f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    if threads <= 1 || items.len() <= 1 {
+        return items.iter().map(f).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let (next, f) = (&next, &f);
+
+    let mut results: Vec<(usize, R)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads.min(items.len())).map(|_| scope.spawn(move || {
+            let mut results = Vec::new();
+            loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                match items.get(index) {
+                    Some(item) => results.push((index, f(item))),
+                    None => break results,
+                }
+            }
+        })).collect();
+
+        workers.into_iter()
+            .flat_map(|worker| worker.join().unwrap_or_else(|err| panic::resume_unwind(err)))
+            .collect()
+    });
+
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
@@ -0,0 +1,125 @@
+//! Restartable copying for `CopyMode::RESTARTABLE_MODE` in the native engine
+//!
+//! Large files are copied in chunks. After each chunk has been flushed to disk
+//! a marker next to the destination file records how far the copy got,
+//! so an interrupted copy continues from there on the next run.
+//! Until the copy is complete the destination file has the [`partial_file_time`].
+
+use std::{ffi::{OsStr, OsString}, fs::{self, File}, io::{self, Read, Seek, SeekFrom}, path::{Path, PathBuf}, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+/// Appended to the name of the destination file to get the name of its marker
+pub const MARKER_SUFFIX: &str = ".robocopyrs-restart";
+
+/// Files larger than this are copied restartably, one chunk at a time
+pub const CHUNK_SIZE: u64 = 1024 * 1024;
+
+/// Modification time of a file that is still being copied, 1980-01-01 like robocopy uses.
+/// The file is older than its source, so `/xo` does not skip it on the next run.
+pub fn partial_file_time() -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(315_532_800)
+}
+
+const TEMPORARY_MARKER_SUFFIX: &str = ".robocopyrs-restart.tmp";
+const MARKER_HEADER: &str = "robocopyrs restart marker 1";
+
+/// Records the progress of an interrupted copy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RestartMarker {
+    pub source_size: u64,
+    /// Modification time of the source as seconds and nanoseconds since the unix epoch
+    pub source_modified: (u64, u32),
+    /// Bytes of the destination known to be written and flushed
+    pub offset: u64,
+}
+
+impl RestartMarker {
+    pub fn new(source_size: u64, source_modified: SystemTime, offset: u64) -> Self {
+        let since_epoch = source_modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+
+        Self {
+            source_size,
+            source_modified: (since_epoch.as_secs(), since_epoch.subsec_nanos()),
+            offset,
+        }
+    }
+
+    /// Path of the marker of a destination file
+    pub fn path_for(destination: &Path) -> PathBuf {
+        let mut name = destination.file_name().map(OsString::from).unwrap_or_default();
+        name.push(MARKER_SUFFIX);
+        destination.with_file_name(name)
+    }
+
+    /// Names of the marker of a destination file and of that marker while it is written
+    pub fn names_for(file_name: &OsStr) -> [OsString; 2] {
+        [MARKER_SUFFIX, TEMPORARY_MARKER_SUFFIX].map(|suffix| {
+            let mut name = file_name.to_os_string();
+            name.push(suffix);
+            name
+        })
+    }
+
+    /// Reads the marker of a destination file, if there is a valid one
+    pub fn read(destination: &Path) -> io::Result<Option<Self>> {
+        let contents = match fs::read_to_string(Self::path_for(destination)) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        let mut lines = contents.lines();
+        if lines.next() != Some(MARKER_HEADER) {
+            return Ok(None);
+        }
+        let values: Vec<u64> = match lines.next().map(|line| line.split(' ').map(str::parse).collect()) {
+            Some(Ok(values)) => values,
+            _ => return Ok(None),
+        };
+
+        Ok(match values[..] {
+            [source_size, secs, nanos, offset] if nanos < 1_000_000_000 => Some(Self {
+                source_size,
+                source_modified: (secs, nanos as u32),
+                offset,
+            }),
+            _ => None,
+        })
+    }
+
+    /// Writes the marker of a destination file, replacing the previous one atomically
+    pub fn write(&self, destination: &Path) -> io::Result<()> {
+        let path = Self::path_for(destination);
+        let mut temporary = destination.as_os_str().to_os_string();
+        temporary.push(TEMPORARY_MARKER_SUFFIX);
+
+        fs::write(&temporary, format!("{}\n{} {} {} {}\n", MARKER_HEADER, self.source_size, self.source_modified.0, self.source_modified.1, self.offset))?;
+        fs::rename(&temporary, &path)
+    }
+
+    /// Removes the marker of a destination file
+    pub fn remove(destination: &Path) -> io::Result<()> {
+        match fs::remove_file(Self::path_for(destination)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    /// The offset to continue copying from.
+    /// It is zero unless the marker belongs to the same version of the source and
+    /// the chunk before the offset is identical in the source and the destination.
+    pub fn resume_offset(&self, source: &mut File, destination: &mut File, current: &Self) -> io::Result<u64> {
+        if self.source_size != current.source_size || self.source_modified != current.source_modified ||
+                self.offset > self.source_size || destination.metadata()?.len() < self.offset {
+            return Ok(0);
+        }
+
+        let start = self.offset.saturating_sub(CHUNK_SIZE);
+        let (mut expected, mut written) = (Vec::new(), Vec::new());
+        source.seek(SeekFrom::Start(start))?;
+        source.by_ref().take(self.offset - start).read_to_end(&mut expected)?;
+        destination.seek(SeekFrom::Start(start))?;
+        destination.by_ref().take(self.offset - start).read_to_end(&mut written)?;
+
+        Ok(if expected == written { self.offset } else { 0 })
+    }
+}
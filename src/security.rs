@@ -0,0 +1,119 @@
+//! Security information on Linux
+//!
+//! Robocopy copies NTFS access control lists with `/copy:S` and auditing information with `/copy:U`.
+//! Native execution copies the closest unix equivalents, which are all extended attributes:
+//! POSIX ACLs and the `user.*` attributes for S, and the `security.*` attributes for U.
+//!
+//! A destination file system without support for them, or `security.*` attributes
+//! the process is not permitted to set, do not fail the copy.
+//! What could not be copied is reported as a [`Degradation`] instead.
+
+use std::{fmt, io, path::{Path, PathBuf}};
+
+use crate::attributes::{ARCHIVE_XATTR, ATTRIBUTES_XATTR};
+
+/// Extended attribute holding the POSIX access ACL
+pub const ACL_ACCESS_XATTR: &str = "system.posix_acl_access";
+
+/// Extended attribute holding the POSIX default ACL of a directory
+pub const ACL_DEFAULT_XATTR: &str = "system.posix_acl_default";
+
+/// The security information of a file robocopy can copy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecurityInfo {
+    /// `S`, POSIX ACLs and `user.*` extended attributes
+    AccessControl,
+    /// `U`, `security.*` extended attributes
+    Auditing,
+}
+
+/// Security information that was not copied although it was asked for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Degradation {
+    /// Path of the destination file or directory
+    pub path: PathBuf,
+    /// Name of the extended attribute, or `owner` for the ownership
+    pub name: String,
+    pub reason: String,
+}
+
+impl fmt::Display for Degradation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} was not copied to {}: {}", self.name, self.path.display(), self.reason)
+    }
+}
+
+impl SecurityInfo {
+    /// Whether an extended attribute belongs to the security information.
+    /// The attributes of the attribute mapping are copied with `A`, not with `S`.
+    pub fn includes(&self, name: &str) -> bool {
+        match self {
+            Self::AccessControl => name == ACL_ACCESS_XATTR || name == ACL_DEFAULT_XATTR ||
+                (name.starts_with("user.") && name != ATTRIBUTES_XATTR && name != ARCHIVE_XATTR),
+            Self::Auditing => name.starts_with("security."),
+        }
+    }
+
+    /// Whether failing to copy an attribute with this error degrades the copy rather than failing it
+    #[cfg(unix)]
+    fn degrades(&self, err: &io::Error) -> bool {
+        match err.kind() {
+            io::ErrorKind::Unsupported => true,
+            io::ErrorKind::PermissionDenied => *self == Self::Auditing,
+            _ => false,
+        }
+    }
+
+    /// Makes the security information of the destination the same as the one of the source,
+    /// removing what the source does not have.
+    /// Returns what could not be copied.
+    #[cfg(unix)]
+    pub fn copy(&self, source: &Path, destination: &Path) -> io::Result<Vec<Degradation>> {
+        let mut degraded = Vec::new();
+        let mut degrade = |name: &str, err: io::Error| match self.degrades(&err) {
+            true => {
+                degraded.push(Degradation { path: destination.to_path_buf(), name: String::from(name), reason: err.to_string() });
+                Ok(())
+            },
+            false => Err(err),
+        };
+
+        let source_names = self.names(source)?;
+        for name in &source_names {
+            if let Some(value) = xattr::get(source, name)? {
+                if let Err(err) = xattr::set(destination, name, &value) {
+                    degrade(name, err)?;
+                }
+            }
+        }
+
+        for name in self.names(destination)?.iter().filter(|name| !source_names.contains(name)) {
+            if let Err(err) = xattr::remove(destination, name) {
+                degrade(name, err)?;
+            }
+        }
+
+        Ok(degraded)
+    }
+
+    /// Does nothing where there are no extended attributes, which is reported once per file
+    #[cfg(not(unix))]
+    pub fn copy(&self, _source: &Path, destination: &Path) -> io::Result<Vec<Degradation>> {
+        Ok(vec![Degradation {
+            path: destination.to_path_buf(),
+            name: String::from(match self { Self::AccessControl => "S", Self::Auditing => "U" }),
+            reason: String::from("Extended attributes are not supported on this platform."),
+        }])
+    }
+
+    /// Names of the extended attributes of a path that belong to the security information,
+    /// none if the file system does not support extended attributes
+    #[cfg(unix)]
+    fn names(&self, path: &Path) -> io::Result<Vec<String>> {
+        match xattr::list(path) {
+            Ok(names) => Ok(names.filter_map(|name| name.into_string().ok()).filter(|name| self.includes(name)).collect()),
+            Err(err) if err.kind() == io::ErrorKind::Unsupported => Ok(Vec::new()),
+            Err(err) => Err(err),
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! Byte sizes
+//!
+//! Used wherever robocopy takes a size, such as the `/max` and `/min`
+//! filters or the I/O and low free space options.
+
+use std::{ffi::OsString, fmt, str::FromStr};
+
+/// A number of bytes
+///
+/// Parses from strings such as `"512KiB"`, `"10MB"` or `"1.5G"`.
+/// Suffixes without a `B` or ending in `iB` are binary multiples like in robocopy,
+/// suffixes ending in `B` alone are decimal multiples.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct ByteSize(pub u64);
+
+impl ByteSize {
+    pub const KIB: u64 = 1 << 10;
+    pub const MIB: u64 = 1 << 20;
+    pub const GIB: u64 = 1 << 30;
+    pub const TIB: u64 = 1 << 40;
+
+    pub const KB: u64 = 1_000;
+    pub const MB: u64 = 1_000_000;
+    pub const GB: u64 = 1_000_000_000;
+    pub const TB: u64 = 1_000_000_000_000;
+
+    pub fn bytes(&self) -> u64 {
+        self.0
+    }
+
+    fn multiplier(suffix: &str) -> Option<u64> {
+        Some(match suffix.to_ascii_lowercase().as_str() {
+            "" | "b" => 1,
+            "k" | "kib" => Self::KIB,
+            "m" | "mib" => Self::MIB,
+            "g" | "gib" => Self::GIB,
+            "t" | "tib" => Self::TIB,
+            "kb" => Self::KB,
+            "mb" => Self::MB,
+            "gb" => Self::GB,
+            "tb" => Self::TB,
+            _ => return None,
+        })
+    }
+}
+
+impl From<u64> for ByteSize {
+    fn from(bytes: u64) -> Self {
+        Self(bytes)
+    }
+}
+
+impl FromStr for ByteSize {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let split = s.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(s.len());
+        let (number, suffix) = s.split_at(split);
+
+        if number.is_empty() {
+            return Err("A size must start with a number.");
+        }
+        let multiplier = Self::multiplier(suffix.trim()).ok_or("Unknown size suffix.")?;
+
+        match number.split_once('.') {
+            None => number.parse::<u64>().ok()
+                .and_then(|n| n.checked_mul(multiplier))
+                .map(Self)
+                .ok_or("Size is too large."),
+            Some(_) => {
+                let value = number.parse::<f64>().map_err(|_| "A size must start with a number.")? * multiplier as f64;
+                if value >= u64::MAX as f64 {
+                    Err("Size is too large.")
+                } else if multiplier == 1 && value.fract() != 0.0 {
+                    Err("A size in bytes must be a whole number.")
+                } else {
+                    Ok(Self(value.round() as u64))
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<&ByteSize> for OsString {
+    fn from(bs: &ByteSize) -> Self {
+        OsString::from(bs.0.to_string())
+    }
+}
+impl From<ByteSize> for OsString {
+    fn from(bs: ByteSize) -> Self {
+        (&bs).into()
+    }
+}
@@ -0,0 +1,113 @@
+//! Run totals in the layout of the robocopy summary table
+
+use std::ops::AddAssign;
+
+use serde_json::{json, Value};
+
+use crate::manifest::parse_size;
+
+/// A row of the summary table
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Totals {
+    pub total: u64,
+    pub copied: u64,
+    pub skipped: u64,
+    pub mismatch: u64,
+    pub failed: u64,
+    pub extras: u64,
+}
+
+impl Totals {
+    /// The row as JSON, with the column names as keys
+    pub fn to_json(&self) -> Value {
+        json!({
+            "total": self.total,
+            "copied": self.copied,
+            "skipped": self.skipped,
+            "mismatch": self.mismatch,
+            "failed": self.failed,
+            "extras": self.extras,
+        })
+    }
+
+    /// The values of a row like `   Bytes :   1.50 m         0 ...`
+    fn parse(row: &str) -> Option<Self> {
+        let mut values = Vec::new();
+        let mut tokens = row.split_whitespace().peekable();
+        while let Some(token) = tokens.next() {
+            match tokens.peek() {
+                Some(unit) if ["k", "m", "g", "t"].contains(unit) => values.push(parse_size(&format!("{} {}", token, tokens.next()?))),
+                _ => values.push(token.parse().ok()?),
+            }
+        }
+
+        match values[..] {
+            [total, copied, skipped, mismatch, failed, extras] => Some(Self { total, copied, skipped, mismatch, failed, extras }),
+            _ => None,
+        }
+    }
+}
+
+impl AddAssign for Totals {
+    fn add_assign(&mut self, rhs: Self) {
+        self.total += rhs.total;
+        self.copied += rhs.copied;
+        self.skipped += rhs.skipped;
+        self.mismatch += rhs.mismatch;
+        self.failed += rhs.failed;
+        self.extras += rhs.extras;
+    }
+}
+
+/// The summary table robocopy prints at the end of a run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub dirs: Totals,
+    pub files: Totals,
+    pub bytes: Totals,
+}
+
+impl AddAssign for Summary {
+    fn add_assign(&mut self, rhs: Self) {
+        self.dirs += rhs.dirs;
+        self.files += rhs.files;
+        self.bytes += rhs.bytes;
+    }
+}
+
+impl Summary {
+    /// The summary as JSON, a row for each of `dirs`, `files` and `bytes`
+    pub fn to_json(&self) -> Value {
+        json!({
+            "dirs": self.dirs.to_json(),
+            "files": self.files.to_json(),
+            "bytes": self.bytes.to_json(),
+        })
+    }
+
+    /// The last summary table in the console output or log of robocopy,
+    /// none if it has no complete one
+    pub fn from_log(log: &str) -> Option<Self> {
+        let (mut dirs, mut files, mut bytes) = (None, None, None);
+
+        for line in log.lines() {
+            let (label, row) = match line.split_once(':') {
+                Some(split) => split,
+                None => continue,
+            };
+            // the header has a `Files :` line too, which is not a row
+            let totals = match Totals::parse(row) {
+                Some(totals) => totals,
+                None => continue,
+            };
+            match label.trim() {
+                "Dirs" => dirs = Some(totals),
+                "Files" => files = Some(totals),
+                "Bytes" => bytes = Some(totals),
+                _ => (),
+            }
+        }
+
+        Some(Self { dirs: dirs?, files: files?, bytes: bytes? })
+    }
+}
@@ -0,0 +1,49 @@
+//! Bandwidth throttling for the native engine
+
+use std::{sync::Mutex, thread, time::{Duration, Instant}};
+
+/// A token bucket that limits the bytes per second of all threads sharing it
+#[derive(Debug)]
+pub struct TokenBucket {
+    bytes_per_sec: f64,
+    capacity: f64,
+    state: Mutex<(f64, Instant)>,
+}
+
+impl TokenBucket {
+    /// Creates a full bucket that allows bursts of up to `capacity` bytes
+    pub fn new(bytes_per_sec: u64, capacity: u64) -> Self {
+        Self {
+            bytes_per_sec: bytes_per_sec.max(1) as f64,
+            capacity: capacity.max(1) as f64,
+            state: Mutex::new((capacity.max(1) as f64, Instant::now())),
+        }
+    }
+
+    pub fn bytes_per_sec(&self) -> u64 {
+        self.bytes_per_sec as u64
+    }
+
+    /// Takes `bytes` tokens out of the bucket, sleeping until they have been refilled.
+    /// Requests larger than the capacity are allowed and paid back by sleeping longer.
+    pub fn acquire(&self, bytes: u64) {
+        let wait = {
+            let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+            let (tokens, last) = &mut *state;
+            let now = Instant::now();
+
+            *tokens = (*tokens + now.duration_since(*last).as_secs_f64() * self.bytes_per_sec).min(self.capacity) - bytes as f64;
+            *last = now;
+
+            if *tokens < 0.0 {
+                Duration::from_secs_f64(-*tokens / self.bytes_per_sec)
+            } else {
+                Duration::ZERO
+            }
+        };
+
+        if !wait.is_zero() {
+            thread::sleep(wait);
+        }
+    }
+}
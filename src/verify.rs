@@ -0,0 +1,116 @@
+//! Content verification
+//!
+//! Robocopy decides what to copy by size and timestamps and trusts the copy.
+//! Verification hashes every copied file at the source and the destination
+//! after native execution copied it, to catch copies that were silently corrupted.
+
+use std::{fmt, fs::File, io::{self, Read}, path::Path};
+
+use sha2::Digest as _;
+
+/// The hash function files are compared with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HashAlgorithm {
+    #[default]
+    Blake3,
+    Sha256,
+}
+
+impl HashAlgorithm {
+    /// Hashes the contents of a file
+    pub fn hash_file(&self, path: &Path) -> io::Result<Digest> {
+        let mut file = File::open(path)?;
+
+        Ok(Digest(match self {
+            Self::Blake3 => {
+                let mut hasher = blake3::Hasher::new();
+                read_chunks(&mut file, |chunk| { hasher.update(chunk); })?;
+                hasher.finalize().as_bytes().to_vec()
+            },
+            Self::Sha256 => {
+                let mut hasher = sha2::Sha256::new();
+                read_chunks(&mut file, |chunk| hasher.update(chunk))?;
+                hasher.finalize().to_vec()
+            },
+        }))
+    }
+}
+
+/// Reads a file to its end, passing each chunk on
+fn read_chunks(file: &mut File, mut f: impl FnMut(&[u8])) -> io::Result<()> {
+    let mut buffer = vec![0; 64 * 1024];
+
+    loop {
+        match file.read(&mut buffer) {
+            Ok(0) => return Ok(()),
+            Ok(read) => f(&buffer[..read]),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Blake3 => "BLAKE3",
+            Self::Sha256 => "SHA-256",
+        })
+    }
+}
+
+/// The hash of the contents of a file
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Digest(pub Vec<u8>);
+
+impl fmt::Display for Digest {
+    /// Lowercase hexadecimal
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+    }
+}
+
+/// Verification settings
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Verification {
+    pub algorithm: HashAlgorithm,
+    /// Copy a file once more when its copy does not match
+    pub recopy: bool,
+}
+
+/// A copy that does not match its source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HashMismatch {
+    pub algorithm: HashAlgorithm,
+    pub source: Digest,
+    pub destination: Digest,
+}
+
+impl fmt::Display for HashMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The copy does not match the source, {} {} differs from {}.", self.algorithm, self.destination, self.source)
+    }
+}
+
+impl std::error::Error for HashMismatch {}
+
+impl Verification {
+    /// Hashes a source and its copy, returning the digest if they match.
+    /// A mismatch is an `InvalidData` error with a [`HashMismatch`] inside.
+    pub fn verify(&self, source: &Path, destination: &Path) -> io::Result<Digest> {
+        let (source, destination) = (self.algorithm.hash_file(source)?, self.algorithm.hash_file(destination)?);
+
+        if source == destination {
+            Ok(source)
+        } else {
+            Err(io::Error::new(io::ErrorKind::InvalidData, HashMismatch { algorithm: self.algorithm, source, destination }))
+        }
+    }
+}
+
+impl HashMismatch {
+    /// The mismatch inside an error returned by [`Verification::verify`]
+    pub fn of(err: &io::Error) -> Option<&Self> {
+        err.get_ref().and_then(|inner| inner.downcast_ref::<Self>())
+    }
+}
@@ -0,0 +1,162 @@
+//! Robocopy compatible wildcard matching
+//!
+//! Implements the way robocopy matches the `files` patterns and the names
+//! and paths given to `/xf` and `/xd`:
+//! - matching is case-insensitive
+//! - `*` matches any sequence of characters, including none
+//! - `?` matches exactly one character
+//! - `*.*` matches every name, including names without an extension
+//! - patterns containing a path separator are matched against the full path,
+//!   all other patterns only against the file or directory name
+//!
+//! Windows also matches patterns against the 8.3 short name of a file,
+//! so `*.htm` can select `page.html` through its short name `PAGE~1.HTM`.
+//! Short names do not exist outside of Windows and this quirk is not supported,
+//! neither are the DOS `?` and `.` rules at the end of a pattern.
+
+use std::path::Path;
+
+/// A single wildcard pattern
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Wildcard {
+    original: String,
+    pattern: Vec<char>,
+    full_path: bool,
+}
+
+impl Wildcard {
+    pub fn new(pattern: &str) -> Self {
+        let original = String::from(pattern);
+        let full_path = pattern.contains(['\\', '/']);
+        let pattern = if pattern == "*.*" { "*" } else { pattern };
+
+        Self {
+            original,
+            pattern: Self::normalize(pattern.trim_end_matches(['\\', '/'])),
+            full_path,
+        }
+    }
+
+    /// The pattern as it was given
+    pub fn as_str(&self) -> &str {
+        &self.original
+    }
+
+    /// Whether the pattern is matched against the full path rather than the name
+    pub fn is_path_pattern(&self) -> bool {
+        self.full_path
+    }
+
+    /// Matches the pattern against a file or directory name.
+    /// Path patterns never match a name alone.
+    pub fn matches_name(&self, name: &str) -> bool {
+        !self.full_path && Self::matches_chars(&self.pattern, &Self::normalize(name))
+    }
+
+    /// Matches the pattern against a full path.
+    /// Name patterns are matched against the last component of the path.
+    pub fn matches_path(&self, path: &Path) -> bool {
+        if self.full_path {
+            Self::matches_chars(&self.pattern, &Self::normalize(path.to_string_lossy().trim_end_matches(['\\', '/'])))
+        } else {
+            path.file_name().is_some_and(|name| self.matches_name(&name.to_string_lossy()))
+        }
+    }
+
+    /// Lower cases the characters and unifies the path separators
+    fn normalize(s: &str) -> Vec<char> {
+        s.chars()
+            .flat_map(char::to_lowercase)
+            .map(|c| if c == '\\' { '/' } else { c })
+            .collect()
+    }
+
+    fn matches_chars(pattern: &[char], text: &[char]) -> bool {
+        let (mut p, mut t) = (0, 0);
+        let mut last_star: Option<(usize, usize)> = None;
+
+        while t < text.len() {
+            match pattern.get(p) {
+                Some('*') => {
+                    last_star = Some((p, t));
+                    p += 1;
+                },
+                Some('?') => {
+                    p += 1;
+                    t += 1;
+                },
+                Some(c) if *c == text[t] => {
+                    p += 1;
+                    t += 1;
+                },
+                _ => match last_star {
+                    Some((star_p, star_t)) => {
+                        last_star = Some((star_p, star_t + 1));
+                        p = star_p + 1;
+                        t = star_t + 1;
+                    },
+                    None => return false,
+                }
+            }
+        }
+
+        pattern[p..].iter().all(|c| *c == '*')
+    }
+}
+
+impl From<&str> for Wildcard {
+    fn from(pattern: &str) -> Self {
+        Self::new(pattern)
+    }
+}
+
+/// A list of wildcard patterns that matches when any of its patterns match
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WildcardSet {
+    patterns: Vec<Wildcard>,
+}
+
+impl WildcardSet {
+    pub fn new<S: AsRef<str>>(patterns: &[S]) -> Self {
+        Self {
+            patterns: patterns.iter().map(|pattern| Wildcard::new(pattern.as_ref())).collect(),
+        }
+    }
+
+    /// The selection made by the `files` patterns of a command,
+    /// when there are no patterns every file is selected like with `*.*`
+    pub fn files<S: AsRef<str>>(patterns: &[S]) -> Self {
+        if patterns.is_empty() {
+            Self::new(&["*.*"])
+        } else {
+            Self::new(patterns)
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    pub fn patterns(&self) -> &[Wildcard] {
+        &self.patterns
+    }
+
+    /// Returns the first pattern matching the name
+    pub fn match_name(&self, name: &str) -> Option<&Wildcard> {
+        self.patterns.iter().find(|pattern| pattern.matches_name(name))
+    }
+
+    /// Returns the first pattern matching the path,
+    /// see [`Wildcard::matches_path`]
+    pub fn match_path(&self, path: &Path) -> Option<&Wildcard> {
+        self.patterns.iter().find(|pattern| pattern.matches_path(path))
+    }
+
+    pub fn matches_name(&self, name: &str) -> bool {
+        self.match_name(name).is_some()
+    }
+
+    pub fn matches_path(&self, path: &Path) -> bool {
+        self.match_path(path).is_some()
+    }
+}
@@ -0,0 +1,51 @@
+//! Ages and dates of the `/maxage`, `/minage`, `/maxlad` and `/minlad` filters
+
+use std::{convert::TryFrom, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use robocopyrs::filter::AgeLimit;
+
+const DAY: Duration = Duration::from_secs(86_400);
+
+#[test]
+fn dates_must_exist() {
+    assert_eq!(AgeLimit::date(2024, 2, 29), Ok(AgeLimit::Date { year: 2024, month: 2, day: 29 }));
+    assert_eq!(AgeLimit::date(2000, 2, 29), Ok(AgeLimit::Date { year: 2000, month: 2, day: 29 }));
+    assert!(AgeLimit::date(2023, 2, 29).is_err());
+    assert!(AgeLimit::date(1900, 2, 29).is_err());
+    assert!(AgeLimit::date(2024, 4, 31).is_err());
+    assert!(AgeLimit::date(2024, 1, 0).is_err());
+    assert!(AgeLimit::date(2024, 0, 1).is_err());
+    assert!(AgeLimit::date(2024, 13, 1).is_err());
+    assert!(AgeLimit::date(1899, 12, 31).is_err());
+    assert!(AgeLimit::Date { year: 2023, month: 2, day: 29 }.validate().is_err());
+}
+
+#[test]
+fn days_stop_where_robocopy_reads_years() {
+    assert_eq!(AgeLimit::days(0), Ok(AgeLimit::Days(0)));
+    assert_eq!(AgeLimit::days(1899), Ok(AgeLimit::Days(1899)));
+    assert!(AgeLimit::days(1900).is_err());
+    assert!(AgeLimit::Days(1900).validate().is_err());
+    assert_eq!(AgeLimit::try_from(10 * DAY + Duration::from_secs(3600)), Ok(AgeLimit::Days(10)));
+    assert!(AgeLimit::try_from(1900 * DAY).is_err());
+}
+
+#[test]
+fn robocopy_notation() {
+    assert_eq!("1899".parse(), Ok(AgeLimit::Days(1899)));
+    assert_eq!("7".parse(), Ok(AgeLimit::Days(7)));
+    assert_eq!("20240229".parse(), Ok(AgeLimit::Date { year: 2024, month: 2, day: 29 }));
+    for invalid in ["20230229", "20241301", "20240100", "1900", "190001", "", "-1", "7d", "2024-02-29"] {
+        assert!(invalid.parse::<AgeLimit>().is_err(), "{}", invalid);
+    }
+}
+
+#[test]
+fn points_in_time_become_their_utc_date() {
+    let leap_day = UNIX_EPOCH + 19_782 * DAY;
+    assert_eq!(AgeLimit::try_from(leap_day + Duration::from_secs(86_399)), Ok(AgeLimit::Date { year: 2024, month: 2, day: 29 }));
+    assert_eq!(AgeLimit::Date { year: 2024, month: 2, day: 29 }.cutoff(SystemTime::now()), leap_day);
+    assert_eq!(AgeLimit::try_from(UNIX_EPOCH - Duration::from_secs(1)), Ok(AgeLimit::Date { year: 1969, month: 12, day: 31 }));
+    assert!(AgeLimit::try_from(UNIX_EPOCH - 30_000 * DAY).is_err());
+    assert_eq!(AgeLimit::Days(3).cutoff(leap_day), leap_day - 3 * DAY);
+}
@@ -0,0 +1,65 @@
+//! The emulated ARCHIVE attribute of `/a` and `/m` in native execution
+
+#![cfg(unix)]
+
+mod common;
+
+use std::{env, fs, path::{Path, PathBuf}};
+
+use robocopyrs::{FileAttributes, RobocopyCommand};
+use robocopyrs::attributes::{AttributeMapping, ARCHIVE_XATTR};
+use robocopyrs::filter::Filter;
+use common::TestDir;
+
+/// Set to a directory on a file system without user extended attributes, such as a ramfs mount
+const NO_XATTR_DIR: &str = "ROBOCOPYRS_NO_XATTR_DIR";
+
+#[test]
+fn m_clears_archive_only_where_it_can_be_recorded() {
+    let dir = TestDir::new("archive");
+    let file = dir.source.join("a.txt");
+    fs::write(&file, b"a").unwrap();
+    if xattr::set(&file, "user.robocopyrs.probe", b"").is_err() {
+        eprintln!("skipped, the file system has no user extended attributes");
+        return;
+    }
+
+    assert_eq!(m_run(&dir), 1);
+    assert!(!archive(&file));
+    assert!(xattr::get(&file, ARCHIVE_XATTR).unwrap().is_some());
+    assert_eq!(m_run(&dir), 0);
+}
+
+#[test]
+fn m_copies_without_extended_attributes() {
+    let base = env::var_os(NO_XATTR_DIR).map(PathBuf::from).unwrap_or_else(env::temp_dir);
+    let dir = TestDir::in_dir(&base, "archive-no-xattr");
+    let file = dir.source.join("a.txt");
+    fs::write(&file, b"a").unwrap();
+    if xattr::set(&file, "user.robocopyrs.probe", b"").is_ok() {
+        eprintln!("skipped, the file system has user extended attributes, set {} to one without", NO_XATTR_DIR);
+        return;
+    }
+
+    assert_eq!(m_run(&dir), 1);
+    assert_eq!(fs::read(dir.destination.join("a.txt")).unwrap(), b"a");
+    assert!(archive(&file));
+}
+
+fn archive(path: &Path) -> bool {
+    AttributeMapping::default().read(path, &fs::metadata(path).unwrap()).unwrap().contains_any(&FileAttributes::ARCHIVE)
+}
+
+/// Runs `/m` from the source to the destination of the test and returns the exit code
+fn m_run(dir: &TestDir) -> i8 {
+    let command = RobocopyCommand {
+        source: &dir.source,
+        destination: &dir.destination,
+        filter: Some(Filter { handle_archive_and_reset: true, ..Filter::default() }),
+        ..RobocopyCommand::default()
+    };
+
+    let report = command.execute_native();
+    assert_eq!(report.failures().count(), 0, "{:?}", report.failures().collect::<Vec<_>>());
+    report.exit_code_bits()
+}
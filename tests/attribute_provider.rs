@@ -0,0 +1,104 @@
+//! Attribute providers for `/ia` and `/xa` on Linux
+
+#![cfg(unix)]
+
+mod common;
+
+use std::{fs, os::unix::fs::PermissionsExt, path::Path, sync::Arc};
+
+use robocopyrs::{FileAttributes, RobocopyCommand};
+use robocopyrs::attributes::{AttributeProvider, Heuristics, SambaDosAttrib, SAMBA_XATTR};
+use robocopyrs::filter::{FileExclusionFilter, Filter};
+use common::TestDir;
+
+/// An NDR encoded `xattr_DosAttrib` of the version with the attributes at offset 8
+fn ndr(version: u16, valid_flags: u32, attrib: u32) -> Vec<u8> {
+    let mut value = Vec::new();
+    value.extend_from_slice(&version.to_le_bytes());
+    value.extend_from_slice(&version.to_le_bytes());
+    value.extend_from_slice(&valid_flags.to_le_bytes());
+    value.extend_from_slice(&attrib.to_le_bytes());
+    value.extend_from_slice(&[0; 16]);
+    value
+}
+
+#[test]
+fn samba_values_of_every_version() {
+    assert_eq!(SambaDosAttrib::parse(b"0x22\0"), Some(0x22));
+    assert_eq!(SambaDosAttrib::parse(&ndr(4, 1, 0x2)), Some(0x2));
+    assert_eq!(SambaDosAttrib::parse(&ndr(5, 1, 0x21)), Some(0x21));
+    assert_eq!(SambaDosAttrib::parse(&ndr(4, 0, 0x2)), None);
+    assert_eq!(SambaDosAttrib::parse(&[1, 0, 1, 0, 0x4, 0, 0, 0]), Some(0x4));
+    assert_eq!(SambaDosAttrib::parse(&[9, 0]), None);
+    assert_eq!(SambaDosAttrib::parse(&[]), None);
+}
+
+#[test]
+fn heuristics_hide_dotfiles_and_protect_files_without_write_bits() {
+    let dir = TestDir::new("heuristics");
+    fs::write(dir.source.join(".profile"), b"").unwrap();
+    fs::write(dir.source.join("locked"), b"").unwrap();
+    let mut permissions = fs::metadata(dir.source.join("locked")).unwrap().permissions();
+    permissions.set_readonly(true);
+    fs::set_permissions(dir.source.join("locked"), permissions).unwrap();
+
+    let attributes = |name: &str| Heuristics.attributes(&dir.source.join(name), &fs::metadata(dir.source.join(name)).unwrap()).unwrap().flags();
+    assert_eq!(attributes(".profile"), FileAttributes::HIDDEN.flags());
+    assert_eq!(attributes("locked"), FileAttributes::READ_ONLY.flags());
+}
+
+#[test]
+fn filters_read_attributes_through_the_provider() {
+    let dir = TestDir::new("samba");
+    let (source, destination) = (&dir.source, &dir.destination);
+    fs::write(source.join("hidden.txt"), b"").unwrap();
+    fs::write(source.join("plain.txt"), b"").unwrap();
+    if xattr::set(source.join("hidden.txt"), SAMBA_XATTR, &ndr(4, 1, 0x22)).is_err() {
+        eprintln!("skipped, the file system has no user extended attributes");
+        return;
+    }
+
+    let command = RobocopyCommand {
+        source,
+        destination,
+        filter: Some(Filter {
+            file_exclusion_filter: Some(FileExclusionFilter::Attributes(FileAttributes::HIDDEN)),
+            ..Filter::default()
+        }),
+        attribute_provider: Some(Arc::new(SambaDosAttrib)),
+        ..RobocopyCommand::default()
+    };
+    let copies: Vec<_> = command.plan().unwrap().copies().filter(|entry| !entry.is_dir).map(|entry| entry.path.clone()).collect();
+    assert_eq!(copies, vec![Path::new("plain.txt")]);
+}
+
+#[test]
+fn unreadable_files_are_planned_without_their_stored_attributes() {
+    if unsafe { libc::geteuid() } == 0 {
+        eprintln!("skipped, root can read every file");
+        return;
+    }
+
+    let dir = TestDir::new("unreadable");
+    let (source, destination) = (&dir.source, &dir.destination);
+    fs::write(source.join("locked.txt"), b"locked").unwrap();
+    fs::write(source.join("plain.txt"), b"plain").unwrap();
+    fs::set_permissions(source.join("locked.txt"), fs::Permissions::from_mode(0o000)).unwrap();
+
+    let plain = RobocopyCommand { source, destination, ..RobocopyCommand::default() };
+    let filtered = RobocopyCommand {
+        filter: Some(Filter {
+            file_exclusion_filter: Some(FileExclusionFilter::Attributes(FileAttributes::HIDDEN)),
+            ..Filter::default()
+        }),
+        ..plain.clone()
+    };
+    for command in [&plain, &filtered] {
+        let plan = command.plan().unwrap();
+        assert_eq!(plan.copies().filter(|entry| !entry.is_dir).count(), 2);
+    }
+
+    let report = plain.execute_native();
+    assert_eq!(report.exit_code_bits(), 9);
+    assert_eq!(fs::read(destination.join("plain.txt")).unwrap(), b"plain");
+}
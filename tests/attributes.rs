@@ -0,0 +1,111 @@
+//! Windows file attributes mapped onto Linux files in native execution
+
+#![cfg(unix)]
+
+mod common;
+
+use std::{fs, os::unix::fs::PermissionsExt, path::Path};
+
+use robocopyrs::{FileAttributes, PostCopyActions, RobocopyCommand};
+use robocopyrs::attributes::{AttributeMapping, HiddenPolicy, ATTRIBUTES_XATTR};
+use robocopyrs::filter::{FileExclusionFilter, Filter};
+use common::TestDir;
+
+fn has_user_xattrs(path: &Path) -> bool {
+    if xattr::set(path, "user.robocopyrs.probe", b"").is_err() {
+        eprintln!("skipped, the file system has no user extended attributes");
+        return false;
+    }
+    xattr::remove(path, "user.robocopyrs.probe").unwrap();
+    true
+}
+
+fn attributes(mapping: &AttributeMapping, path: &Path) -> [bool; 8] {
+    mapping.read(path, &fs::metadata(path).unwrap()).unwrap().flags()
+}
+
+#[test]
+fn read_only_maps_onto_permissions_and_the_others_onto_an_extended_attribute() {
+    let dir = TestDir::new("attributes");
+    let file = dir.source.join("file.txt");
+    fs::write(&file, b"file").unwrap();
+    if !has_user_xattrs(&file) {
+        return;
+    }
+
+    let mapping = AttributeMapping::default();
+    let flags = FileAttributes::_MULTIPLE([true, false, true, true, false, false, false, false]);
+    mapping.write(&file, &flags).unwrap();
+    assert!(fs::metadata(&file).unwrap().permissions().readonly());
+    assert_eq!(xattr::get(&file, ATTRIBUTES_XATTR).unwrap(), Some(b"SH".to_vec()));
+    assert_eq!(attributes(&mapping, &file), flags.flags());
+
+    mapping.apply(&file, &FileAttributes::TEMPORARY, &FileAttributes::_MULTIPLE([true, false, true, false, false, false, false, false])).unwrap();
+    assert!(!fs::metadata(&file).unwrap().permissions().readonly());
+    assert_eq!(attributes(&mapping, &file), [false, false, false, true, false, false, false, true]);
+
+    mapping.write(&file, &FileAttributes::none()).unwrap();
+    assert_eq!(xattr::get(&file, ATTRIBUTES_XATTR).unwrap(), None);
+}
+
+#[test]
+fn hidden_policies() {
+    let dir = TestDir::new("hidden");
+    fs::write(dir.source.join(".profile"), b"").unwrap();
+    fs::write(dir.source.join("plain"), b"").unwrap();
+
+    let hidden = |policy: HiddenPolicy, name: &str| attributes(&AttributeMapping::new(policy), &dir.source.join(name))[3];
+    assert!(hidden(HiddenPolicy::DotFiles, ".profile"));
+    assert!(!hidden(HiddenPolicy::DotFiles, "plain"));
+    assert!(!hidden(HiddenPolicy::Ignore, ".profile"));
+    assert!(!hidden(HiddenPolicy::ExtendedAttribute, ".profile"));
+}
+
+#[test]
+fn filters_and_post_copy_actions_use_the_mapping() {
+    let dir = TestDir::new("attribute-copy");
+    let (source, destination) = (&dir.source, &dir.destination);
+    fs::write(source.join(".hidden"), b"hidden").unwrap();
+    fs::write(source.join("plain.txt"), b"plain").unwrap();
+
+    let command = RobocopyCommand {
+        source,
+        destination,
+        filter: Some(Filter {
+            file_exclusion_filter: Some(FileExclusionFilter::Attributes(FileAttributes::HIDDEN)),
+            ..Filter::default()
+        }),
+        post_copy_actions: Some(PostCopyActions::AddAttribsToFiles(FileAttributes::READ_ONLY)),
+        attribute_mapping: Some(AttributeMapping::new(HiddenPolicy::DotFiles)),
+        ..RobocopyCommand::default()
+    };
+    assert_eq!(command.execute_native().exit_code_bits(), 1);
+    assert!(!destination.join(".hidden").exists());
+    assert!(fs::metadata(destination.join("plain.txt")).unwrap().permissions().readonly());
+    assert!(!fs::metadata(source.join("plain.txt")).unwrap().permissions().readonly());
+}
+
+#[test]
+fn read_only_directories_are_protected_after_their_files_are_copied() {
+    if !common::unprivileged("read_only_directories_are_protected_after_their_files_are_copied") {
+        return;
+    }
+
+    let dir = TestDir::new("read-only-dir");
+    let (source, destination) = (&dir.source, &dir.destination);
+    fs::create_dir_all(source.join("locked/inner")).unwrap();
+    fs::write(source.join("locked/a.txt"), b"a").unwrap();
+    fs::write(source.join("locked/inner/b.txt"), b"b").unwrap();
+    for locked in ["locked/inner", "locked"] {
+        fs::set_permissions(source.join(locked), fs::Permissions::from_mode(0o555)).unwrap();
+    }
+
+    let command = RobocopyCommand { source, destination, ..RobocopyCommand::default() };
+    let report = command.execute_native();
+    assert_eq!(report.failures().count(), 0, "{:?}", report.failures().collect::<Vec<_>>());
+    assert_eq!(report.exit_code_bits(), 1);
+    assert_eq!(fs::read(destination.join("locked/inner/b.txt")).unwrap(), b"b");
+    for locked in ["locked", "locked/inner"] {
+        assert_eq!(fs::metadata(destination.join(locked)).unwrap().permissions().mode() & 0o777, 0o555);
+    }
+}
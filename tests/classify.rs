@@ -0,0 +1,120 @@
+//! Robocopy's file classes and the switches that copy or exclude them
+
+mod common;
+
+use std::{fs, path::Path, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use robocopyrs::{FileAttributes, RobocopyCommand};
+use robocopyrs::classify::{Classifier, FileClass};
+use robocopyrs::evaluate::{EntryMetadata, Rule};
+use robocopyrs::filter::{FileAndDirectoryExclusionFilter, FileExclusionFilter, FileExclusionFilterException, Filter};
+use common::TestDir;
+
+fn base() -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+}
+
+fn file(size: u64, modified: SystemTime) -> EntryMetadata {
+    EntryMetadata::file(size, modified)
+}
+
+/// A source and destination pair of every class
+fn pairs() -> Vec<(FileClass, Option<EntryMetadata>, Option<EntryMetadata>)> {
+    let later = base() + Duration::from_secs(1);
+    let hidden = EntryMetadata { attributes: FileAttributes::HIDDEN, ..file(1, base()) };
+    let changed = |seconds| EntryMetadata { changed: Some(base() + Duration::from_secs(seconds)), ..file(1, base()) };
+
+    vec![
+        (FileClass::Lonely, Some(file(1, base())), None),
+        (FileClass::Extra, None, Some(file(1, base()))),
+        (FileClass::Newer, Some(file(1, later)), Some(file(1, base()))),
+        (FileClass::Older, Some(file(1, base())), Some(file(1, later))),
+        (FileClass::Changed, Some(file(1, base())), Some(file(2, base()))),
+        (FileClass::Tweaked, Some(hidden), Some(file(1, base()))),
+        (FileClass::Modified, Some(changed(1)), Some(changed(2))),
+        (FileClass::Same, Some(file(1, base())), Some(file(1, base()))),
+        (FileClass::Mismatched, Some(file(1, base())), Some(EntryMetadata::directory(base()))),
+    ]
+}
+
+/// The classes a classifier acts on
+fn acted_on(classifier: &Classifier) -> Vec<FileClass> {
+    pairs().into_iter().filter_map(|(class, source, destination)| {
+        assert_eq!(classifier.classify(source.as_ref(), destination.as_ref()), Some(class));
+        classifier.decide(source.as_ref(), destination.as_ref()).unwrap().ok()
+    }).collect()
+}
+
+#[test]
+fn pairs_are_classified_by_size_time_and_attributes() {
+    acted_on(&Classifier::default());
+    assert_eq!(Classifier::default().classify(None, None), None);
+}
+
+#[test]
+fn by_default_only_same_tweaked_and_modified_files_are_left_alone() {
+    assert_eq!(acted_on(&Classifier::default()), vec![
+        FileClass::Lonely, FileClass::Extra, FileClass::Newer, FileClass::Older, FileClass::Changed, FileClass::Mismatched,
+    ]);
+}
+
+#[test]
+fn exclusions_and_inclusions_of_the_filter() {
+    let filter = Filter {
+        file_and_directory_exclusion_filter: Some(FileAndDirectoryExclusionFilter::_MULTIPLE([true, true, false])),
+        file_exclusion_filter: Some(FileExclusionFilter::_MULTIPLE(None, Vec::new(), [true, true, true, false])),
+        file_exclusion_filter_exceptions: Some(FileExclusionFilterException::_MULTIPLE([true, true, true])),
+        ..Filter::default()
+    };
+
+    assert_eq!(acted_on(&Classifier::from(&filter)), vec![
+        FileClass::Tweaked, FileClass::Modified, FileClass::Same, FileClass::Mismatched,
+    ]);
+}
+
+#[test]
+fn each_switch_excludes_with_its_rule() {
+    let checks = [
+        (Classifier { exclude_lonely: true, ..Classifier::default() }, FileClass::Lonely, Rule::ExcludeLonely),
+        (Classifier { exclude_extra: true, ..Classifier::default() }, FileClass::Extra, Rule::ExcludeExtra),
+        (Classifier { exclude_newer: true, ..Classifier::default() }, FileClass::Newer, Rule::ExcludeNewer),
+        (Classifier { exclude_older: true, ..Classifier::default() }, FileClass::Older, Rule::ExcludeOlder),
+        (Classifier { exclude_changed: true, ..Classifier::default() }, FileClass::Changed, Rule::ExcludeChanged),
+        (Classifier::default(), FileClass::Tweaked, Rule::IncludeTweaked),
+        (Classifier::default(), FileClass::Modified, Rule::IncludeModified),
+        (Classifier::default(), FileClass::Same, Rule::IncludeSame),
+    ];
+
+    for (classifier, class, rule) in checks {
+        assert_eq!(classifier.check(class, false), Err(rule));
+    }
+    assert_eq!(Classifier { include_tweaked: true, ..Classifier::default() }.check(FileClass::Tweaked, false), Ok(()));
+    assert_eq!(Classifier { include_modified: true, ..Classifier::default() }.check(FileClass::Modified, false), Ok(()));
+    assert_eq!(Classifier { include_same: true, ..Classifier::default() }.check(FileClass::Same, false), Ok(()));
+}
+
+#[test]
+fn directories_on_both_sides_are_always_walked_into() {
+    let (source, destination) = (EntryMetadata::directory(base()), EntryMetadata::directory(base() + Duration::from_secs(1)));
+
+    assert_eq!(Classifier::default().decide(Some(&source), Some(&destination)), Some(Ok(FileClass::Same)));
+    assert_eq!(Classifier { exclude_lonely: true, ..Classifier::default() }.decide(Some(&source), None), Some(Err(Rule::ExcludeLonely)));
+    assert_eq!(Classifier { exclude_extra: true, ..Classifier::default() }.decide(None, Some(&destination)), Some(Err(Rule::ExcludeExtra)));
+}
+
+#[test]
+fn copied_files_replan_as_same_until_their_attributes_change() {
+    let dir = TestDir::new("classify");
+    let (source, destination) = (&dir.source, &dir.destination);
+    fs::write(source.join("file.txt"), b"contents").unwrap();
+
+    let command = RobocopyCommand { source, destination, ..RobocopyCommand::default() };
+    assert_eq!(command.execute_native().exit_code_bits(), 1);
+    let class = || command.plan().unwrap().entries.into_iter().find(|entry| entry.path == Path::new("file.txt")).unwrap().class;
+    assert_eq!(class(), FileClass::Same);
+
+    let mut permissions = fs::metadata(source.join("file.txt")).unwrap().permissions();
+    permissions.set_readonly(true);
+    fs::set_permissions(source.join("file.txt"), permissions).unwrap();
+    assert_eq!(class(), FileClass::Tweaked);
+}
@@ -0,0 +1,86 @@
+//! The robocopy command line and the `robocopyrs` binary
+
+mod common;
+
+use std::{fs, path::Path, process::Command};
+
+use robocopyrs::{cli, log, Engine, FileProperties};
+use robocopyrs::filter::FileExclusionFilter;
+use robocopyrs::performance::PerformanceChoice;
+use robocopyrs::summary::Summary;
+use common::TestDir;
+
+#[test]
+fn robocopy_syntax_becomes_a_command() {
+    let args = ["src", "dst", "*.txt", "*.md", "/MIR", "/xf", "*.tmp", "~*", "/MT:16", "/copy:dat", "/r:3", "/np", "--native", "--json"];
+    let invocation = cli::parse(&args).unwrap();
+    let command = &invocation.command;
+
+    assert_eq!((command.source, command.destination), (Path::new("src"), Path::new("dst")));
+    assert_eq!(command.files, vec!["*.txt", "*.md"]);
+    assert!(command.empty_dir_copy && command.remove_files_and_dirs_not_in_src && command.overwrite_destination_dir_sec_settings_when_mirror);
+    assert_eq!(command.only_copy_top_n_levels, None);
+    assert!(matches!(command.filter.as_ref().unwrap().file_exclusion_filter, Some(FileExclusionFilter::_MULTIPLE(None, ref names, [false, false, false, false])) if names == &["*.tmp", "~*"]));
+    assert_eq!(command.performance_options.unwrap().performance_choice(), PerformanceChoice::Threads(16));
+    assert_eq!(command.copy_file_properties.unwrap().flags(), FileProperties::_MULTIPLE([true, true, true, false, false, false]).flags());
+    assert_eq!(command.retry_settings.unwrap().retries(), 3);
+    assert_eq!((invocation.engine, invocation.json), (Engine::Native, true));
+
+    let top_level_only = cli::parse(&["src", "dst"]).unwrap();
+    assert_eq!(top_level_only.command.only_copy_top_n_levels, Some(1));
+}
+
+#[test]
+fn invalid_arguments_name_their_position() {
+    let error = |args: &[&str]| cli::parse(args).unwrap_err();
+
+    assert_eq!((error(&["src", "dst", "/s", "/foo"]).position, error(&["src", "dst", "/s", "/foo"]).argument.as_str()), (4, "/foo"));
+    assert_eq!(error(&["src", "dst", "/s:2"]).reason, "The switch takes no value.");
+    assert_eq!(error(&["src", "dst", "/r"]).reason, "The switch needs a value.");
+    assert_eq!(error(&["src", "dst", "/MT:200"]).position, 3);
+    assert_eq!(error(&["src", "dst", "/xa:Q"]).reason, "File attributes are letters of RASHCNET.");
+    assert_eq!(error(&["src"]).reason, "No Destination Directory Specified.");
+}
+
+#[test]
+fn the_binary_copies_natively_and_exits_with_robocopy_codes() {
+    let dir = TestDir::new("cli");
+    let (source, destination) = (&dir.source, &dir.destination);
+    fs::create_dir_all(source.join("sub")).unwrap();
+    fs::write(source.join("a.txt"), vec![0; 3000]).unwrap();
+    fs::write(source.join("b.tmp"), b"b").unwrap();
+    fs::write(source.join("sub/c.txt"), b"c").unwrap();
+
+    let run = |extra: &[&str]| Command::new(env!("CARGO_BIN_EXE_robocopyrs"))
+        .arg(source).arg(destination).args(["/e", "/xf", "*.tmp", "--native"]).args(extra)
+        .output().unwrap();
+
+    let copied = run(&["--json"]);
+    assert_eq!(copied.status.code(), Some(1));
+    let json: serde_json::Value = serde_json::from_slice(&copied.stdout).unwrap();
+    assert_eq!(json["exit_code"], 1);
+    assert_eq!(json["summary"]["files"]["copied"], 2);
+    assert_eq!(json["summary"]["bytes"]["copied"], 3001);
+    assert!(destination.join("sub/c.txt").is_file() && !destination.join("b.tmp").exists());
+
+    let unchanged = run(&[]);
+    assert_eq!(unchanged.status.code(), Some(0));
+    let summary = Summary::from_log(&String::from_utf8_lossy(&unchanged.stdout)).unwrap();
+    assert_eq!((summary.files.copied, summary.files.skipped), (0, summary.files.total));
+
+    assert_eq!(run(&["/unknown"]).status.code(), Some(16));
+}
+
+#[test]
+fn summaries_read_back_from_the_log_text() {
+    let dir = TestDir::new("cli-log");
+    let (source, destination) = (&dir.source, &dir.destination);
+    fs::write(source.join("large"), vec![0; 5 << 20]).unwrap();
+
+    let args = [source.to_str().unwrap(), destination.to_str().unwrap()];
+    let command = cli::parse(&args).unwrap().command;
+    let report = command.execute_native();
+
+    assert_eq!(Summary::from_log(&log::text(&command, &report)), Some(report.summary));
+    assert_eq!(Summary::from_log("no summary"), None);
+}
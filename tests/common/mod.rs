@@ -0,0 +1,99 @@
+//! Fixtures shared by the integration tests
+
+#![allow(dead_code)]
+
+use std::{env, fs, io, path::{Path, PathBuf}, process};
+
+/// A directory of its own for a test with an empty source directory in it,
+/// removed with everything in it when dropped, also when the test fails
+pub struct TestDir {
+    pub root: PathBuf,
+    pub source: PathBuf,
+    pub destination: PathBuf,
+}
+
+impl TestDir {
+    /// Creates the directory in the temporary directory of the system
+    pub fn new(name: &str) -> Self {
+        Self::in_dir(&env::temp_dir(), name)
+    }
+
+    /// Creates the directory below `base`, replacing what an earlier run left behind
+    pub fn in_dir(base: &Path, name: &str) -> Self {
+        let root = base.join(format!("robocopyrs-{}-{}", name, process::id()));
+        let _ = remove(&root);
+        let (source, destination) = (root.join("source"), root.join("destination"));
+        fs::create_dir_all(&source).unwrap();
+
+        Self { root, source, destination }
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = remove(&self.root);
+    }
+}
+
+/// Whether the calling test should go on in this process, which it does unless the process is root.
+/// Root is not kept out by permissions, so a test about them is run again as `nobody`
+/// from a copy of the test binary, and fails if that run fails.
+#[cfg(unix)]
+pub fn unprivileged(test: &str) -> bool {
+    use std::os::unix::process::CommandExt;
+
+    const NOBODY: u32 = 65534;
+
+    if unsafe { libc::geteuid() } != 0 {
+        return true;
+    }
+
+    let dir = TestDir::new(&format!("unprivileged-{}", test));
+    let binary = dir.root.join("tests");
+    fs::copy(env::current_exe().unwrap(), &binary).unwrap();
+    let status = process::Command::new(&binary)
+        .args(["--exact", test, "--test-threads=1"])
+        .uid(NOBODY)
+        .gid(NOBODY)
+        .status()
+        .unwrap();
+    assert!(status.success(), "{} failed as an unprivileged user", test);
+    false
+}
+
+/// Removes a directory tree, first making its directories accessible if that is in the way
+fn remove(path: &Path) -> io::Result<()> {
+    match fs::remove_dir_all(path) {
+        Err(err) if err.kind() == io::ErrorKind::PermissionDenied => {
+            make_accessible(path);
+            fs::remove_dir_all(path)
+        },
+        result => result,
+    }
+}
+
+#[cfg(unix)]
+fn make_accessible(path: &Path) {
+    use std::os::unix::fs::PermissionsExt;
+
+    if fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_dir()) {
+        let _ = fs::set_permissions(path, fs::Permissions::from_mode(0o755));
+        for entry in fs::read_dir(path).into_iter().flatten().flatten() {
+            make_accessible(&entry.path());
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn make_accessible(path: &Path) {
+    if let Ok(metadata) = fs::symlink_metadata(path) {
+        let mut permissions = metadata.permissions();
+        permissions.set_readonly(false);
+        let _ = fs::set_permissions(path, permissions);
+        if metadata.is_dir() {
+            for entry in fs::read_dir(path).into_iter().flatten().flatten() {
+                make_accessible(&entry.path());
+            }
+        }
+    }
+}
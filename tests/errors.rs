@@ -0,0 +1,109 @@
+//! Entries that fail or cannot be copied in native execution, without stopping the run
+
+#![cfg(unix)]
+
+mod common;
+
+use std::{ffi::CString, fs, os::unix::{ffi::OsStrExt, fs::PermissionsExt, net::UnixListener}, path::Path, sync::mpsc, thread, time::Duration};
+
+use robocopyrs::{log, RobocopyCommand};
+use robocopyrs::evaluate::Rule;
+use robocopyrs::native::Outcome;
+use robocopyrs::performance::{PerformanceChoice, PerformanceOptions};
+use robocopyrs::plan::Action;
+use common::TestDir;
+
+#[test]
+fn unreadable_directories_fail_and_their_siblings_are_copied() {
+    if unsafe { libc::geteuid() } == 0 {
+        eprintln!("skipped, root can read every directory");
+        return;
+    }
+
+    let dir = TestDir::new("unreadable-dir");
+    let (source, destination) = (&dir.source, &dir.destination);
+    fs::create_dir_all(source.join("locked")).unwrap();
+    fs::create_dir_all(source.join("open")).unwrap();
+    fs::write(source.join("a.txt"), b"a").unwrap();
+    fs::write(source.join("locked/b.txt"), b"b").unwrap();
+    fs::write(source.join("open/c.txt"), b"c").unwrap();
+    fs::set_permissions(source.join("locked"), fs::Permissions::from_mode(0o000)).unwrap();
+
+    for threads in [1, 4] {
+        let _ = fs::remove_dir_all(destination);
+        let command = RobocopyCommand {
+            source,
+            destination,
+            empty_dir_copy: true,
+            performance_options: Some(PerformanceOptions::PerformanceChoiceOnly(PerformanceChoice::Threads(threads))),
+            ..RobocopyCommand::default()
+        };
+
+        let report = command.execute_native();
+        assert_eq!(report.exit_code_bits(), 9);
+        let failures: Vec<&Path> = report.failures().map(|entry| entry.entry.path.as_path()).collect();
+        assert_eq!(failures, vec![Path::new("locked")]);
+        assert_eq!((report.summary.dirs.failed, report.summary.files.copied), (1, 2));
+        assert_eq!(fs::read(destination.join("a.txt")).unwrap(), b"a");
+        assert_eq!(fs::read(destination.join("open/c.txt")).unwrap(), b"c");
+    }
+}
+
+fn mkfifo(path: &Path) {
+    let path = CString::new(path.as_os_str().as_bytes()).unwrap();
+    assert_eq!(unsafe { libc::mkfifo(path.as_ptr(), 0o644) }, 0);
+}
+
+#[test]
+fn special_files_are_skipped_without_being_opened() {
+    let dir = TestDir::new("special");
+    let (source, destination) = (&dir.source, &dir.destination);
+    fs::create_dir_all(destination).unwrap();
+    fs::write(source.join("a.txt"), b"a").unwrap();
+    fs::write(source.join("c.txt"), b"c").unwrap();
+    mkfifo(&source.join("fifo"));
+    mkfifo(&destination.join("c.txt"));
+    let _socket = UnixListener::bind(source.join("socket")).unwrap();
+
+    let (sender, receiver) = mpsc::channel();
+    let (run_source, run_destination) = (source.clone(), destination.clone());
+    thread::spawn(move || {
+        let command = RobocopyCommand { source: &run_source, destination: &run_destination, fix_file_times: true, ..RobocopyCommand::default() };
+        let report = command.execute_native();
+        sender.send((report.exit_code_bits(), log::text(&command, &report), report)).unwrap();
+    });
+    let (bits, text, report) = receiver.recv_timeout(Duration::from_secs(30)).expect("the run blocked on a special file");
+
+    assert_eq!(bits, 1);
+    let mut skipped: Vec<&Path> = report.entries.iter()
+        .filter(|entry| entry.entry.action == Action::Skip(Some(Rule::SpecialFile)))
+        .inspect(|entry| assert_eq!(entry.outcome, Outcome::Skipped))
+        .map(|entry| entry.entry.path.as_path())
+        .collect();
+    skipped.sort();
+    assert_eq!(skipped, vec![Path::new("c.txt"), Path::new("fifo"), Path::new("socket")]);
+    assert_eq!(text.matches("*Special").count(), 3);
+    assert_eq!(fs::read(destination.join("a.txt")).unwrap(), b"a");
+    assert!(!destination.join("fifo").exists() && !destination.join("socket").exists());
+}
+
+#[test]
+fn files_that_cannot_be_created_fail_with_their_own_error() {
+    if !common::unprivileged("files_that_cannot_be_created_fail_with_their_own_error") {
+        return;
+    }
+
+    let dir = TestDir::new("uncreatable");
+    let (source, destination) = (&dir.source, &dir.destination);
+    fs::write(source.join("new.txt"), b"new").unwrap();
+    fs::create_dir_all(destination).unwrap();
+    fs::set_permissions(destination, fs::Permissions::from_mode(0o555)).unwrap();
+
+    let command = RobocopyCommand { source, destination, ..RobocopyCommand::default() };
+    let report = command.execute_native();
+    assert_eq!(report.exit_code_bits(), 8);
+    let entry = report.failures().next().unwrap();
+    assert_eq!(entry.outcome, Outcome::Failed(std::io::Error::from_raw_os_error(libc::EACCES).to_string()));
+    let text = log::text(&command, &report);
+    assert!(text.contains("ERROR 13 (0x0000000D)"), "{}", text);
+}
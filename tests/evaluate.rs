@@ -0,0 +1,92 @@
+//! Evaluating the selection rules against fixture metadata
+
+use std::{path::Path, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use robocopyrs::FileAttributes;
+use robocopyrs::evaluate::{Decision, EntryMetadata, Evaluator, FileClass, Rule, TimeTolerance};
+use robocopyrs::filter::{AgeLimit, FileExclusionFilter, FileExclusionFilterException, Filter};
+use robocopyrs::size::ByteSize;
+
+fn base() -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+}
+
+fn file(size: u64, modified: SystemTime) -> EntryMetadata {
+    EntryMetadata::file(size, modified)
+}
+
+fn changed(seconds: u64) -> EntryMetadata {
+    EntryMetadata { changed: Some(base() + Duration::from_secs(seconds)), ..file(1, base()) }
+}
+
+fn evaluate(evaluator: &Evaluator, source: Option<EntryMetadata>, destination: Option<EntryMetadata>) -> Decision {
+    evaluator.evaluate_file(Path::new("dir/file.txt"), source.as_ref(), destination.as_ref())
+}
+
+#[test]
+fn each_class_by_default_and_with_is_it_im() {
+    let later = base() + Duration::from_secs(1);
+    let hidden = EntryMetadata { attributes: FileAttributes::HIDDEN, ..file(1, base()) };
+    let table = [
+        (Some(file(1, base())), None, FileClass::Lonely, None),
+        (None, Some(file(1, base())), FileClass::Extra, None),
+        (Some(file(1, later)), Some(file(1, base())), FileClass::Newer, None),
+        (Some(file(1, base())), Some(file(1, later)), FileClass::Older, None),
+        (Some(file(1, base())), Some(file(2, base())), FileClass::Changed, None),
+        (Some(hidden), Some(file(1, base())), FileClass::Tweaked, Some(Rule::IncludeTweaked)),
+        (Some(changed(1)), Some(changed(2)), FileClass::Modified, Some(Rule::IncludeModified)),
+        (Some(file(1, base())), Some(file(1, base())), FileClass::Same, Some(Rule::IncludeSame)),
+        // change times only count when both sides have one
+        (Some(changed(1)), Some(file(1, base())), FileClass::Same, Some(Rule::IncludeSame)),
+        (Some(changed(1)), Some(changed(1)), FileClass::Same, Some(Rule::IncludeSame)),
+    ];
+
+    let default = Evaluator::new(None, &["*"], None);
+    let including = Evaluator::new(Some(&Filter {
+        file_exclusion_filter_exceptions: Some(FileExclusionFilterException::_MULTIPLE([true, true, true])),
+        ..Filter::default()
+    }), &["*"], None);
+
+    for (source, destination, class, rule) in table {
+        assert_eq!(FileClass::of(source.as_ref(), destination.as_ref()), Some(class));
+        let expected = rule.map(Decision::Exclude).unwrap_or(Decision::Include(class));
+        assert_eq!(evaluate(&default, source, destination), expected, "{:?}", class);
+        assert_eq!(evaluate(&including, source, destination), Decision::Include(class), "{:?}", class);
+    }
+}
+
+#[test]
+fn tolerance_turns_newer_into_same() {
+    let (source, destination) = (file(1, base() + Duration::from_secs(2)), file(1, base()));
+    let evaluator = Evaluator::new(None, &["*"], None);
+
+    assert_eq!(evaluate(&evaluator, Some(source), Some(destination)), Decision::Include(FileClass::Newer));
+    let tolerant = evaluator.with_time_tolerance(TimeTolerance { fat_file_times: true, daylight_saving_time: false });
+    assert_eq!(evaluate(&tolerant, Some(source), Some(destination)), Decision::Exclude(Rule::IncludeSame));
+}
+
+#[test]
+fn source_rules_come_before_the_class() {
+    let now = base() + Duration::from_secs(10 * 86_400);
+    let read_only = EntryMetadata { attributes: FileAttributes::READ_ONLY, ..file(100, base()) };
+    let table = [
+        (Filter { max_size: Some(ByteSize(99)), ..Filter::default() }, file(100, base()), Rule::MaxSize),
+        (Filter { min_size: Some(ByteSize(101)), ..Filter::default() }, file(100, base()), Rule::MinSize),
+        (Filter { max_age: Some(AgeLimit::Days(5)), ..Filter::default() }, file(100, base()), Rule::MaxAge),
+        (Filter { min_age: Some(AgeLimit::Days(20)), ..Filter::default() }, file(100, base()), Rule::MinAge),
+        (Filter { archive_only: true, ..Filter::default() }, file(100, base()), Rule::Archive),
+        (Filter { handle_archive_and_reset: true, ..Filter::default() }, file(100, base()), Rule::ArchiveAndReset),
+        (Filter { include_only_files_with_any_of_these_attribs: Some(FileAttributes::HIDDEN), ..Filter::default() }, read_only, Rule::IncludeAttributes),
+        (Filter { file_exclusion_filter: Some(FileExclusionFilter::Attributes(FileAttributes::READ_ONLY)), ..Filter::default() }, read_only, Rule::ExcludeAttributes),
+        (Filter { file_exclusion_filter: Some(FileExclusionFilter::PathOrName(vec![String::from("*.txt")])), ..Filter::default() }, file(100, base()), Rule::ExcludeFiles(String::from("*.txt"))),
+    ];
+
+    for (filter, source, rule) in table {
+        let evaluator = Evaluator::new(Some(&filter), &["*"], None).with_now(now);
+        assert_eq!(evaluate(&evaluator, Some(source), None), Decision::Exclude(rule));
+    }
+
+    let evaluator = Evaluator::new(None, &["*.md"], Some(1));
+    assert_eq!(evaluate(&evaluator, Some(file(1, base())), None), Decision::Exclude(Rule::Levels));
+    assert_eq!(evaluator.evaluate_file(Path::new("file.txt"), Some(&file(1, base())), None), Decision::Exclude(Rule::Files));
+}
@@ -0,0 +1,140 @@
+//! Source filters applied in native execution
+
+mod common;
+
+use std::{fs::{self, File}, path::Path, time::{Duration, SystemTime}};
+
+use robocopyrs::RobocopyCommand;
+use robocopyrs::evaluate::Rule;
+use robocopyrs::filter::{AgeLimit, DirectoryExclusionFilter, FileExclusionFilter, Filter};
+use robocopyrs::native::{Outcome, RunReport};
+use robocopyrs::size::ByteSize;
+use robocopyrs::plan::Action;
+use common::TestDir;
+
+const DAY: Duration = Duration::from_secs(86_400);
+
+/// The files the run skipped and the rule that skipped them, sorted by path
+fn skipped_files(report: &RunReport) -> Vec<(&Path, Rule)> {
+    let mut skipped: Vec<(&Path, Rule)> = report.entries.iter()
+        .filter(|entry| !entry.entry.is_dir)
+        .filter_map(|entry| match &entry.entry.action {
+            Action::Skip(Some(rule)) => {
+                assert_eq!(entry.outcome, Outcome::Skipped);
+                Some((entry.entry.path.as_path(), rule.clone()))
+            },
+            _ => None,
+        })
+        .collect();
+    skipped.sort_by(|a, b| a.0.cmp(b.0));
+    skipped
+}
+
+#[test]
+fn age_limits_skip_files_by_modification_time() {
+    let dir = TestDir::new("age");
+    let (source, destination) = (&dir.source, &dir.destination);
+    for (name, modified) in [
+        ("new.txt", SystemTime::now()),
+        ("month.txt", SystemTime::now() - 30 * DAY),
+        ("old.txt", SystemTime::UNIX_EPOCH + 11_000 * DAY),
+    ] {
+        fs::write(source.join(name), name).unwrap();
+        File::open(source.join(name)).unwrap().set_modified(modified).unwrap();
+    }
+
+    let max_age = RobocopyCommand {
+        source,
+        destination,
+        filter: Some(Filter { max_age: Some(AgeLimit::days(7).unwrap()), ..Filter::default() }),
+        ..RobocopyCommand::default()
+    };
+    let report = max_age.execute_native();
+    assert_eq!(report.exit_code_bits(), 1);
+    assert_eq!((report.summary.files.copied, report.summary.files.skipped), (1, 2));
+    assert_eq!(skipped_files(&report), vec![(Path::new("month.txt"), Rule::MaxAge), (Path::new("old.txt"), Rule::MaxAge)]);
+    assert_eq!(fs::read(destination.join("new.txt")).unwrap(), b"new.txt");
+
+    fs::remove_dir_all(destination).unwrap();
+    let min_age = RobocopyCommand {
+        filter: Some(Filter { min_age: Some(AgeLimit::date(2001, 1, 1).unwrap()), ..Filter::default() }),
+        ..max_age.clone()
+    };
+    let report = min_age.execute_native();
+    assert_eq!(report.exit_code_bits(), 1);
+    assert_eq!(skipped_files(&report), vec![(Path::new("month.txt"), Rule::MinAge), (Path::new("new.txt"), Rule::MinAge)]);
+    assert_eq!(fs::read(destination.join("old.txt")).unwrap(), b"old.txt");
+    assert!(!destination.join("new.txt").exists() && !destination.join("month.txt").exists());
+}
+
+#[test]
+fn size_limits_keep_files_within_their_bounds() {
+    let dir = TestDir::new("size");
+    let (source, destination) = (&dir.source, &dir.destination);
+    fs::write(source.join("small.bin"), vec![0; 100]).unwrap();
+    fs::write(source.join("exact.bin"), vec![0; 1024]).unwrap();
+    fs::write(source.join("large.bin"), vec![0; 4096]).unwrap();
+
+    let command = RobocopyCommand {
+        source,
+        destination,
+        filter: Some(Filter {
+            max_size: Some("1KiB".parse().unwrap()),
+            min_size: Some(ByteSize(ByteSize::KIB)),
+            ..Filter::default()
+        }),
+        ..RobocopyCommand::default()
+    };
+    let report = command.execute_native();
+    assert_eq!(report.exit_code_bits(), 1);
+    assert_eq!((report.summary.files.copied, report.summary.bytes.copied), (1, 1024));
+    assert_eq!(skipped_files(&report), vec![(Path::new("large.bin"), Rule::MaxSize), (Path::new("small.bin"), Rule::MinSize)]);
+    assert_eq!(fs::metadata(destination.join("exact.bin")).unwrap().len(), 1024);
+    assert!(!destination.join("small.bin").exists() && !destination.join("large.bin").exists());
+}
+
+#[test]
+fn wildcards_select_files_and_exclude_files_and_directories() {
+    let dir = TestDir::new("wildcards");
+    let (source, destination) = (&dir.source, &dir.destination);
+    for dir in ["build", "cache", "docs/cache"] {
+        fs::create_dir_all(source.join(dir)).unwrap();
+    }
+    for file in ["Makefile", "notes.TXT", "Report.txt", "data.csv", "build/out.txt", "cache/a.txt", "docs/cache/b.txt"] {
+        fs::write(source.join(file), file).unwrap();
+    }
+
+    let cache = source.join("cache").to_string_lossy().into_owned();
+    let command = RobocopyCommand {
+        source,
+        destination,
+        files: vec!["*.txt", "make????"],
+        filter: Some(Filter {
+            file_exclusion_filter: Some(FileExclusionFilter::PathOrName(vec![String::from("*report*")])),
+            directory_exclusion_filter: Some(DirectoryExclusionFilter::PathOrName(vec![String::from("BUILD"), cache.clone()])),
+            ..Filter::default()
+        }),
+        ..RobocopyCommand::default()
+    };
+    let report = command.execute_native();
+    assert_eq!(report.exit_code_bits(), 1);
+
+    let mut copied: Vec<&Path> = report.entries.iter()
+        .filter(|entry| !entry.entry.is_dir && entry.outcome == Outcome::Done)
+        .map(|entry| entry.entry.path.as_path())
+        .collect();
+    copied.sort();
+    assert_eq!(copied, vec![Path::new("Makefile"), Path::new("docs/cache/b.txt"), Path::new("notes.TXT")]);
+    assert_eq!(skipped_files(&report), vec![(Path::new("Report.txt"), Rule::ExcludeFiles(String::from("*report*"))), (Path::new("data.csv"), Rule::Files)]);
+
+    let excluded_dirs: Vec<(&Path, &Action)> = report.entries.iter()
+        .filter(|entry| entry.entry.is_dir && matches!(entry.entry.action, Action::Skip(_)))
+        .map(|entry| (entry.entry.path.as_path(), &entry.entry.action))
+        .collect();
+    assert_eq!(excluded_dirs, vec![
+        (Path::new("build"), &Action::Skip(Some(Rule::ExcludeDirectories(String::from("BUILD"))))),
+        (Path::new("cache"), &Action::Skip(Some(Rule::ExcludeDirectories(cache)))),
+    ]);
+    assert!(!destination.join("build").exists() && !destination.join("cache").exists());
+    assert_eq!(fs::read(destination.join("docs/cache/b.txt")).unwrap(), b"docs/cache/b.txt");
+}
@@ -0,0 +1,137 @@
+//! Symbolic links in native execution
+
+#![cfg(unix)]
+
+mod common;
+
+use std::{fs, os::unix::fs::symlink, path::{Path, PathBuf}};
+
+use robocopyrs::RobocopyCommand;
+use robocopyrs::evaluate::Rule;
+use robocopyrs::filter::{DirectoryExclusionFilter, FileAndDirectoryExclusionFilter, FileExclusionFilter, Filter};
+use robocopyrs::native::{Outcome, RunReport};
+use robocopyrs::performance::{PerformanceChoice, PerformanceOptions};
+use robocopyrs::plan::Action;
+use common::TestDir;
+
+/// A source with links to a file, a directory, nothing and the source itself
+fn links(name: &str) -> TestDir {
+    let dir = TestDir::new(name);
+    let source = &dir.source;
+    fs::create_dir_all(source.join("data")).unwrap();
+    fs::write(source.join("data/file.txt"), b"file").unwrap();
+    symlink("data/file.txt", source.join("file_link")).unwrap();
+    symlink("data", source.join("dir_link")).unwrap();
+    symlink("missing.txt", source.join("broken")).unwrap();
+    symlink(".", source.join("data/loop")).unwrap();
+    symlink("..", source.join("data/up")).unwrap();
+    dir
+}
+
+/// The links the run left alone and the rule that excluded them, sorted by path
+fn skipped_links(report: &RunReport) -> Vec<(&Path, Rule)> {
+    let mut skipped: Vec<(&Path, Rule)> = report.skipped_links()
+        .inspect(|entry| assert_eq!(entry.outcome, Outcome::Skipped))
+        .map(|entry| match &entry.entry.action {
+            Action::Skip(Some(rule)) => (entry.entry.path.as_path(), rule.clone()),
+            action => panic!("{:?} is not a skipped link", action),
+        })
+        .collect();
+    skipped.sort_by(|a, b| a.0.cmp(b.0));
+    skipped
+}
+
+#[test]
+fn links_are_followed_except_broken_links_and_cycles() {
+    let dir = links("follow-links");
+    let (source, destination) = (&dir.source, &dir.destination);
+
+    let command = RobocopyCommand { source, destination, ..RobocopyCommand::default() };
+    let report = command.execute_native();
+    assert_eq!(report.exit_code_bits(), 1);
+    assert_eq!(report.failures().count(), 0);
+    assert_eq!(skipped_links(&report), vec![
+        (Path::new("broken"), Rule::BrokenLink),
+        (Path::new("data/loop"), Rule::LinkCycle),
+        (Path::new("data/up"), Rule::LinkCycle),
+        (Path::new("dir_link/loop"), Rule::LinkCycle),
+        (Path::new("dir_link/up"), Rule::LinkCycle),
+    ]);
+
+    for path in ["file_link", "data/file.txt", "dir_link/file.txt"] {
+        let metadata = fs::symlink_metadata(destination.join(path)).unwrap();
+        assert!(metadata.is_file(), "{} is not a file", path);
+        assert_eq!(fs::read(destination.join(path)).unwrap(), b"file");
+    }
+    assert!(fs::symlink_metadata(destination.join("dir_link")).unwrap().is_dir());
+    assert!(fs::symlink_metadata(destination.join("broken")).is_err());
+    assert!(fs::symlink_metadata(destination.join("data/loop")).is_err());
+}
+
+#[test]
+fn sl_copies_links_as_links() {
+    let dir = links("copy-links");
+    let (source, destination) = (&dir.source, &dir.destination);
+
+    let command = RobocopyCommand {
+        source,
+        destination,
+        performance_options: Some(PerformanceOptions::COPY_RATHER_THAN_FOLLOW_LINK(PerformanceChoice::Default)),
+        ..RobocopyCommand::default()
+    };
+    let report = command.execute_native();
+    assert_eq!(report.exit_code_bits(), 1);
+    assert_eq!(report.failures().count(), 0);
+    assert_eq!(report.skipped_links().count(), 0);
+
+    for (path, target) in [("file_link", "data/file.txt"), ("dir_link", "data"), ("broken", "missing.txt"), ("data/loop", "."), ("data/up", "..")] {
+        assert_eq!(fs::read_link(destination.join(path)).unwrap(), Path::new(target), "{}", path);
+    }
+    assert_eq!(fs::read(destination.join("data/file.txt")).unwrap(), b"file");
+}
+
+#[test]
+fn xj_xjd_and_xjf_exclude_links() {
+    let dir = links("exclude-links");
+    let (source, destination) = (&dir.source, &dir.destination);
+
+    let xj = RobocopyCommand {
+        source,
+        destination,
+        filter: Some(Filter { file_and_directory_exclusion_filter: Some(FileAndDirectoryExclusionFilter::JUNCTION_POINTS), ..Filter::default() }),
+        ..RobocopyCommand::default()
+    };
+    let report = xj.execute_native();
+    assert_eq!(report.exit_code_bits(), 1);
+    assert_eq!(skipped_links(&report), vec![
+        (Path::new("broken"), Rule::ExcludeJunctions),
+        (Path::new("data/loop"), Rule::ExcludeJunctions),
+        (Path::new("data/up"), Rule::ExcludeJunctions),
+        (Path::new("dir_link"), Rule::ExcludeJunctions),
+        (Path::new("file_link"), Rule::ExcludeJunctions),
+    ]);
+
+    fs::remove_dir_all(destination).unwrap();
+    let xjd_xjf = RobocopyCommand {
+        filter: Some(Filter {
+            directory_exclusion_filter: Some(DirectoryExclusionFilter::JUNCTION_POINTS),
+            file_exclusion_filter: Some(FileExclusionFilter::JUNCTION_POINTS),
+            ..Filter::default()
+        }),
+        ..xj.clone()
+    };
+    let report = xjd_xjf.execute_native();
+    assert_eq!(report.exit_code_bits(), 1);
+    assert_eq!(skipped_links(&report), vec![
+        (Path::new("broken"), Rule::ExcludeFileJunctions),
+        (Path::new("data/loop"), Rule::ExcludeDirectoryJunctions),
+        (Path::new("data/up"), Rule::ExcludeDirectoryJunctions),
+        (Path::new("dir_link"), Rule::ExcludeDirectoryJunctions),
+        (Path::new("file_link"), Rule::ExcludeFileJunctions),
+    ]);
+
+    let mut copied: Vec<PathBuf> = fs::read_dir(destination).unwrap().map(|entry| entry.unwrap().file_name().into()).collect();
+    copied.sort();
+    assert_eq!(copied, vec![PathBuf::from("data")]);
+    assert_eq!(fs::read(destination.join("data/file.txt")).unwrap(), b"file");
+}
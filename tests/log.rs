@@ -0,0 +1,36 @@
+//! Robocopy logs of native runs
+
+mod common;
+
+use std::{fs, path::Path};
+
+use robocopyrs::RobocopyCommand;
+use robocopyrs::logging::LoggingSettings;
+use common::TestDir;
+
+#[test]
+fn directory_lines_count_the_files_of_their_source() {
+    let dir = TestDir::new("log-dirs");
+    let (source, destination) = (&dir.source, &dir.destination);
+    fs::create_dir_all(source.join("sub/deeper")).unwrap();
+    for file in ["a.txt", "b.txt", "sub/c.txt", "sub/deeper/d.txt", "sub/deeper/e.txt", "sub/deeper/f.txt"] {
+        fs::write(source.join(file), file).unwrap();
+    }
+
+    let log = dir.root.join("robocopy.log");
+    let command = RobocopyCommand {
+        source,
+        destination,
+        empty_dir_copy: true,
+        logging: Some(LoggingSettings { log: &log, unicode: false, append: false }),
+        ..RobocopyCommand::default()
+    };
+    assert_eq!(command.execute_native().exit_code_bits(), 1);
+    let text = fs::read_to_string(&log).unwrap().replace("\r\n", "\n");
+
+    let counts: Vec<(&str, &Path)> = text.lines().filter(|line| line.contains("New Dir")).map(|line| {
+        let fields: Vec<&str> = line.split('\t').collect();
+        (fields[1].trim_start_matches("  New Dir").trim(), Path::new(fields[2]).strip_prefix(source).unwrap())
+    }).collect();
+    assert_eq!(counts, vec![("2", Path::new("")), ("1", Path::new("sub")), ("3", Path::new("sub/deeper"))]);
+}
@@ -0,0 +1,154 @@
+//! Manifests of native runs and robocopy logs
+
+mod common;
+
+use std::{fs, path::{Path, PathBuf}};
+
+use robocopyrs::RobocopyCommand;
+use robocopyrs::filter::{FileExclusionFilter, Filter};
+use robocopyrs::logging::LoggingSettings;
+use robocopyrs::manifest::{Manifest, ManifestAction};
+use robocopyrs::verify::{HashAlgorithm, Verification};
+use common::TestDir;
+
+#[test]
+fn native_runs_and_their_logs_record_the_same_changes() {
+    let dir = TestDir::new("manifest");
+    let (source, destination) = (&dir.source, &dir.destination);
+    fs::create_dir_all(source.join("sub")).unwrap();
+    fs::create_dir_all(destination).unwrap();
+    fs::write(source.join("a.txt"), b"a").unwrap();
+    fs::write(source.join("b.tmp"), b"b").unwrap();
+    fs::write(source.join("sub/c.txt"), b"c").unwrap();
+    fs::write(destination.join("old.txt"), b"old").unwrap();
+
+    let log = dir.root.join("robocopy.log");
+    let command = RobocopyCommand {
+        source,
+        destination,
+        empty_dir_copy: true,
+        remove_files_and_dirs_not_in_src: true,
+        filter: Some(Filter {
+            file_exclusion_filter: Some(FileExclusionFilter::PathOrName(vec![String::from("*.tmp")])),
+            ..Filter::default()
+        }),
+        verification: Some(Verification::default()),
+        logging: Some(LoggingSettings { log: &log, unicode: false, append: false }),
+        ..RobocopyCommand::default()
+    };
+    let report = command.execute_native();
+    let manifest = Manifest::from_report(&command, &report);
+    assert_eq!(manifest.exit_code, 3);
+
+    let entry = |path: &str| manifest.entries.iter().find(|entry| entry.path == Path::new(path)).unwrap();
+    assert_eq!(entry("a.txt").action, ManifestAction::Copied);
+    assert_eq!(entry("a.txt").hash, Some(HashAlgorithm::Blake3.hash_file(&source.join("a.txt")).unwrap().to_string()));
+    assert_eq!((entry("b.tmp").action, entry("b.tmp").reason.as_deref()), (ManifestAction::Skipped, Some("/xf *.tmp")));
+    assert_eq!((entry("old.txt").action, entry("old.txt").size), (ManifestAction::Purged, 3));
+    assert_eq!(entry("sub/c.txt").action, ManifestAction::Copied);
+    assert!(entry("sub/c.txt").destination_modified.is_some());
+
+    let json = manifest.to_json();
+    assert_eq!(json["exit_code"], 3);
+    assert_eq!(json["entries"].as_array().unwrap().len(), manifest.entries.len());
+
+    let changes = |manifest: &Manifest| {
+        let mut changes: Vec<(PathBuf, ManifestAction)> = manifest.entries.iter()
+            .filter(|entry| !entry.is_dir && matches!(entry.action, ManifestAction::Copied | ManifestAction::Purged))
+            .map(|entry| (entry.path.clone(), entry.action))
+            .collect();
+        changes.sort_by(|a, b| a.0.cmp(&b.0));
+        changes
+    };
+    let logged = Manifest::read_log(&log, 3).unwrap();
+    assert_eq!(changes(&logged), changes(&manifest));
+    assert_eq!(changes(&manifest).len(), 3);
+}
+
+/// A `/mir` log as robocopy writes it on Windows, with a file that copied on its retry
+/// and one that ran out of retries
+const MIRROR_LOG: &str = "
+-------------------------------------------------------------------------------
+   ROBOCOPY     ::     Robust File Copy for Windows                              
+-------------------------------------------------------------------------------
+
+  Started : Sunday, October 18, 2026 3:33:00 PM
+   Source : C:\\Data\\Src\\
+     Dest : D:\\Backup\\
+
+    Files : *.*
+\t    
+  Options : *.* /S /E /DCOPY:DA /COPY:DAT /PURGE /MIR /R:1 /W:1 
+
+------------------------------------------------------------------------------
+
+\t                   3\tC:\\Data\\Src\\
+\t    New File  \t\t      12\tnew.txt
+\t    Newer     \t\t   2.9 k\tchanged.txt
+\t*EXTRA File \t\t     100\told.txt
+\t  New Dir          3\tc:\\data\\src\\Sub\\
+\t    New File  \t\t       5\tlocked.txt
+2026/10/18 15:33:01 ERROR 32 (0x00000020) Copying File C:\\Data\\Src\\Sub\\locked.txt
+The process cannot access the file because it is being used by another process.
+
+Waiting 1 seconds... Retrying...
+\t    New File  \t\t       5\tlocked.txt
+\t    New File  \t\t       7\tdenied.txt
+2026/10/18 15:33:02 ERROR 5 (0x00000005) Copying File C:\\Data\\Src\\Sub\\denied.txt
+Access is denied.
+
+Waiting 1 seconds... Retrying...
+\t    New File  \t\t       7\tdenied.txt
+2026/10/18 15:33:03 ERROR 5 (0x00000005) Copying File C:\\Data\\Src\\Sub\\denied.txt
+Access is denied.
+
+ERROR: RETRY LIMIT EXCEEDED.
+
+\t    *Mismatch \t\t       0\tnode
+\t*EXTRA Dir        -1\td:\\backup\\Old\\
+
+------------------------------------------------------------------------------
+
+               Total    Copied   Skipped  Mismatch    FAILED    Extras
+    Dirs :         2         1         1         0         0         1
+   Files :         6         3         1         1         1         1
+   Bytes :     3.0 k     2.9 k         0         0         7       100
+   Times :   0:00:02   0:00:01                       0:00:00   0:00:00
+   Ended : Sunday, October 18, 2026 3:33:03 PM
+
+";
+
+#[test]
+fn windows_logs_relative_to_their_roots() {
+    let manifest = Manifest::from_log(&MIRROR_LOG.replace('\n', "\r\n"), 15);
+    let entries: Vec<(PathBuf, bool, ManifestAction)> = manifest.entries.iter().map(|entry| (entry.path.clone(), entry.is_dir, entry.action)).collect();
+    let sub = |name: &str| Path::new("Sub").join(name);
+
+    assert_eq!(entries, vec![
+        (PathBuf::new(), true, ManifestAction::Skipped),
+        (PathBuf::from("new.txt"), false, ManifestAction::Copied),
+        (PathBuf::from("changed.txt"), false, ManifestAction::Copied),
+        (PathBuf::from("old.txt"), false, ManifestAction::Purged),
+        (PathBuf::from("Sub"), true, ManifestAction::Copied),
+        (sub("locked.txt"), false, ManifestAction::Copied),
+        (sub("denied.txt"), false, ManifestAction::Failed),
+        (sub("node"), false, ManifestAction::Skipped),
+        (PathBuf::from("Old"), true, ManifestAction::Purged),
+    ]);
+
+    let reason = |index: usize| manifest.entries[index].reason.as_deref();
+    assert_eq!(reason(5), None);
+    assert_eq!(reason(6), Some("ERROR 5 (0x00000005) Copying File C:\\Data\\Src\\Sub\\denied.txt"));
+    assert_eq!((manifest.entries[1].size, manifest.entries[2].size), (12, 2970));
+    assert_eq!(manifest.exit_code, 15);
+    assert!(manifest.arguments.iter().any(|argument| argument == "/MIR"));
+    assert!(manifest.started.is_some() && manifest.ended > manifest.started);
+}
+
+#[test]
+fn paths_outside_the_roots_stay_whole() {
+    let log = MIRROR_LOG.replace("c:\\data\\src\\Sub\\", "E:\\Elsewhere\\");
+    let manifest = Manifest::from_log(&log, 0);
+
+    assert!(manifest.entries.iter().any(|entry| entry.is_dir && entry.path == Path::new("E:\\Elsewhere\\")));
+}
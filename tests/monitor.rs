@@ -0,0 +1,60 @@
+//! Monitoring the source with inotify
+
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::{fs, path::{Path, PathBuf}, thread, time::Duration};
+
+use robocopyrs::RobocopyCommand;
+use robocopyrs::filter::{FileExclusionFilter, Filter};
+use robocopyrs::monitor::MonitorSettings;
+use common::TestDir;
+
+#[test]
+fn passes_copy_the_changed_directories_until_stopped() {
+    let dir = TestDir::new("monitor");
+    let (source, destination) = (&dir.source, &dir.destination);
+    fs::create_dir_all(source.join("a")).unwrap();
+    fs::create_dir_all(source.join("b")).unwrap();
+    fs::write(source.join("a/first.txt"), b"first").unwrap();
+
+    let command = RobocopyCommand {
+        source,
+        destination,
+        filter: Some(Filter {
+            file_exclusion_filter: Some(FileExclusionFilter::PathOrName(vec![String::from("*.tmp")])),
+            ..Filter::default()
+        }),
+        monitor: Some(MonitorSettings { debounce: Some(Duration::from_millis(200)), ..MonitorSettings::default() }),
+        ..RobocopyCommand::default()
+    };
+    let mut monitor = command.monitor_native().unwrap();
+    let stop = monitor.stop_handle();
+
+    let first = monitor.next().unwrap().unwrap();
+    assert_eq!(first.number, 0);
+    assert_eq!(first.report.summary.files.copied, 1);
+    assert!(destination.join("a/first.txt").is_file());
+
+    fs::write(source.join("b/ignored.tmp"), b"excluded").unwrap();
+    fs::create_dir(source.join("b/new")).unwrap();
+    fs::write(source.join("b/new/second.txt"), b"second").unwrap();
+
+    let second = monitor.next().unwrap().unwrap();
+    assert_eq!(second.number, 1);
+    assert_eq!(second.directories, vec![PathBuf::from("b")]);
+    assert!(second.changed.iter().all(|path| path.starts_with("b") && path.extension().is_none_or(|extension| extension != "tmp")));
+    let copied: Vec<&Path> = second.report.entries.iter().filter(|entry| !entry.entry.is_dir && entry.entry.source.is_some())
+        .map(|entry| entry.entry.path.as_path()).collect();
+    assert!(copied.contains(&Path::new("b/new/second.txt")));
+    assert_eq!(second.report.summary.files.copied, 1);
+    assert!(!destination.join("b/ignored.tmp").exists());
+
+    let stopper = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(300));
+        stop.stop();
+    });
+    assert!(monitor.next().is_none());
+    stopper.join().unwrap();
+}
@@ -0,0 +1,73 @@
+//! Moving files and directories with `/mov` and `/move` in native execution
+
+#![cfg(unix)]
+
+mod common;
+
+use std::{fs, os::unix::fs::symlink, path::Path};
+
+use robocopyrs::{Move, RobocopyCommand};
+use robocopyrs::filter::{FileExclusionFilter, Filter};
+use robocopyrs::performance::{PerformanceChoice, PerformanceOptions};
+use common::TestDir;
+
+#[test]
+fn moves_on_the_same_file_system_rename_files_and_links() {
+    let dir = TestDir::new("move-files");
+    let (source, destination) = (&dir.source, &dir.destination);
+    fs::create_dir_all(source.join("sub")).unwrap();
+    fs::write(source.join("a.txt"), b"a").unwrap();
+    fs::write(source.join("sub/b.txt"), b"b").unwrap();
+    symlink("a.txt", source.join("link")).unwrap();
+
+    let command = RobocopyCommand {
+        source,
+        destination,
+        empty_dir_copy: true,
+        mv: Some(Move::FILES),
+        performance_options: Some(PerformanceOptions::COPY_RATHER_THAN_FOLLOW_LINK(PerformanceChoice::Threads(1))),
+        ..RobocopyCommand::default()
+    };
+    let report = command.execute_native();
+
+    assert_eq!(report.exit_code_bits(), 1);
+    assert_eq!(report.summary.files.copied, 3);
+    assert_eq!(fs::read(destination.join("a.txt")).unwrap(), b"a");
+    assert_eq!(fs::read(destination.join("sub/b.txt")).unwrap(), b"b");
+    assert_eq!(fs::read_link(destination.join("link")).unwrap(), Path::new("a.txt"));
+    for moved in ["a.txt", "sub/b.txt", "link"] {
+        assert!(fs::symlink_metadata(source.join(moved)).is_err(), "{}", moved);
+    }
+    assert!(source.join("sub").is_dir());
+}
+
+#[test]
+fn move_removes_the_directories_it_emptied() {
+    let dir = TestDir::new("move-dirs");
+    let (source, destination) = (&dir.source, &dir.destination);
+    fs::create_dir_all(source.join("emptied/deeper")).unwrap();
+    fs::create_dir_all(source.join("kept")).unwrap();
+    fs::write(source.join("emptied/deeper/a.txt"), b"a").unwrap();
+    fs::write(source.join("kept/b.txt"), b"b").unwrap();
+    fs::write(source.join("kept/c.tmp"), b"c").unwrap();
+
+    let command = RobocopyCommand {
+        source,
+        destination,
+        empty_dir_copy: true,
+        mv: Some(Move::FILES_AND_DIRS),
+        filter: Some(Filter {
+            file_exclusion_filter: Some(FileExclusionFilter::PathOrName(vec![String::from("*.tmp")])),
+            ..Filter::default()
+        }),
+        ..RobocopyCommand::default()
+    };
+    let report = command.execute_native();
+
+    assert_eq!((report.exit_code_bits(), report.failures().count()), (1, 0));
+    assert!(!source.join("emptied").exists());
+    assert_eq!(fs::read_dir(source.join("kept")).unwrap().count(), 1);
+    assert!(source.join("kept/c.tmp").is_file());
+    assert_eq!(fs::read(destination.join("emptied/deeper/a.txt")).unwrap(), b"a");
+    assert!(destination.join("kept/b.txt").is_file() && !destination.join("kept/c.tmp").exists());
+}
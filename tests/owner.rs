@@ -0,0 +1,72 @@
+//! Mapping owners for `/copy:O` on Linux
+
+#![cfg(unix)]
+
+mod common;
+
+use std::{fs, io, os::unix::fs::MetadataExt};
+
+use robocopyrs::{FileProperties, RobocopyCommand};
+use robocopyrs::owner::{OwnerLookup, OwnerMapping, Principal};
+use common::TestDir;
+
+/// A user and group id no database has an entry for
+const UNNAMED: u32 = 4_000_123;
+
+#[test]
+fn ids_without_a_name_or_entry_in_the_table_keep_their_id() {
+    let mapping = OwnerMapping {
+        users: vec![(Principal::from("root"), Principal::Id(1234)), (Principal::Id(1), Principal::Id(2))],
+        groups: vec![(Principal::from("root"), Principal::from("daemon"))],
+    };
+
+    assert_eq!(mapping.user(UNNAMED).unwrap(), UNNAMED);
+    assert_eq!(mapping.user(65534).unwrap(), 65534);
+    assert_eq!(mapping.user(0).unwrap(), 1234);
+    assert_eq!(mapping.user(1).unwrap(), 2);
+    assert_eq!(mapping.group(UNNAMED).unwrap(), UNNAMED);
+    assert_eq!(mapping.group(0).unwrap(), 1);
+
+    let lookup = OwnerLookup::new(mapping);
+    for _ in 0..2 {
+        assert_eq!((lookup.user(0).unwrap(), lookup.user(UNNAMED).unwrap(), lookup.group(0).unwrap()), (1234, UNNAMED, 1));
+    }
+}
+
+#[test]
+fn names_that_do_not_resolve_fail() {
+    let mapping = OwnerMapping {
+        users: vec![(Principal::Id(UNNAMED), Principal::from("robocopyrs-no-such-user"))],
+        groups: vec![(Principal::Id(UNNAMED), Principal::from("robocopyrs-no-such-group"))],
+    };
+
+    let err = mapping.user(UNNAMED).unwrap_err();
+    assert_eq!((err.kind(), err.to_string()), (io::ErrorKind::NotFound, String::from("There is no user robocopyrs-no-such-user.")));
+    assert_eq!(mapping.group(UNNAMED).unwrap_err().to_string(), "There is no group robocopyrs-no-such-group.");
+    assert_eq!(mapping.user(0).unwrap(), 0);
+}
+
+#[test]
+fn copies_fail_when_their_owner_does_not_resolve() {
+    let dir = TestDir::new("owner");
+    let (source, destination) = (&dir.source, &dir.destination);
+    fs::write(source.join("a.txt"), b"a").unwrap();
+    fs::write(source.join("b.txt"), b"b").unwrap();
+    let uid = fs::metadata(source.join("a.txt")).unwrap().uid();
+
+    let command = RobocopyCommand {
+        source,
+        destination,
+        copy_file_properties: Some(FileProperties::_MULTIPLE([true, true, true, false, true, false])),
+        owner_mapping: Some(OwnerMapping {
+            users: vec![(Principal::Id(uid), Principal::from("robocopyrs-no-such-user"))],
+            groups: Vec::new(),
+        }),
+        ..RobocopyCommand::default()
+    };
+    let report = command.execute_native();
+
+    assert_eq!(report.exit_code_bits(), 8);
+    assert_eq!((report.summary.dirs.failed, report.summary.files.failed), (1, 2));
+    assert!(report.failures().all(|entry| entry.failed_attempts == ["There is no user robocopyrs-no-such-user."]));
+}
@@ -0,0 +1,65 @@
+//! Dry-run plans of native execution
+
+mod common;
+
+use std::{fs::{self, File}, path::Path, time::{Duration, SystemTime}};
+
+use robocopyrs::RobocopyCommand;
+use robocopyrs::evaluate::FileClass;
+use robocopyrs::plan::Action;
+use common::TestDir;
+
+fn write(path: &Path, contents: &[u8], modified: SystemTime) {
+    fs::write(path, contents).unwrap();
+    File::options().write(true).open(path).unwrap().set_modified(modified).unwrap();
+}
+
+#[test]
+fn plans_without_changing_anything() {
+    let dir = TestDir::new("plan");
+    let (source, destination) = (&dir.source, &dir.destination);
+    let now = SystemTime::now() - Duration::from_secs(60);
+    for path in [source.join("empty"), source.join("sub"), destination.join("gone")] {
+        fs::create_dir_all(path).unwrap();
+    }
+    write(&source.join("changed.txt"), b"longer", now);
+    write(&destination.join("changed.txt"), b"short", now);
+    write(&source.join("newer.txt"), b"newer", now);
+    write(&destination.join("newer.txt"), b"newer", now - Duration::from_secs(3600));
+    write(&source.join("new.txt"), b"new", now);
+    write(&source.join("sub/a.txt"), b"a", now);
+    write(&destination.join("extra.txt"), b"extra", now);
+    write(&destination.join("gone/b.txt"), b"b", now);
+
+    let command = RobocopyCommand {
+        source,
+        destination,
+        remove_files_and_dirs_not_in_src: true,
+        ..RobocopyCommand::default()
+    };
+    let plan = command.plan().unwrap();
+
+    let entries: Vec<(&Path, bool, FileClass, &Action)> = plan.entries.iter()
+        .map(|entry| (entry.path.as_path(), entry.is_dir, entry.class, &entry.action))
+        .collect();
+    assert_eq!(entries, vec![
+        (Path::new(""), true, FileClass::Same, &Action::Skip(None)),
+        (Path::new("changed.txt"), false, FileClass::Changed, &Action::Copy),
+        (Path::new("extra.txt"), false, FileClass::Extra, &Action::Purge),
+        (Path::new("new.txt"), false, FileClass::Lonely, &Action::Copy),
+        (Path::new("newer.txt"), false, FileClass::Newer, &Action::Copy),
+        (Path::new("empty"), true, FileClass::Lonely, &Action::Skip(None)),
+        (Path::new("gone"), true, FileClass::Extra, &Action::Purge),
+        (Path::new("gone/b.txt"), false, FileClass::Extra, &Action::Purge),
+        (Path::new("sub"), true, FileClass::Lonely, &Action::Copy),
+        (Path::new("sub/a.txt"), false, FileClass::Lonely, &Action::Copy),
+    ]);
+    assert_eq!((plan.copies().count(), plan.purges().count()), (5, 3));
+    assert_eq!((plan.summary.dirs.total, plan.summary.dirs.copied, plan.summary.dirs.extras), (3, 1, 1));
+    assert_eq!((plan.summary.files.total, plan.summary.files.copied, plan.summary.files.extras), (4, 4, 2));
+    assert_eq!((plan.summary.bytes.copied, plan.summary.bytes.extras), (15, 6));
+
+    assert!(!destination.join("new.txt").exists() && !destination.join("sub").exists());
+    assert_eq!(fs::read(destination.join("changed.txt")).unwrap(), b"short");
+    assert!(destination.join("extra.txt").exists() && destination.join("gone/b.txt").exists());
+}
@@ -0,0 +1,88 @@
+//! Restartable mode in native execution
+
+mod common;
+
+use std::{env, fs, path::{Path, PathBuf}, process::{Command, Stdio}, thread, time::{Duration, Instant, SystemTime}};
+
+use robocopyrs::{CopyMode, RobocopyCommand};
+use robocopyrs::performance::{PerformanceChoice, PerformanceOptions};
+use robocopyrs::evaluate::FileClass;
+use robocopyrs::restart::{RestartMarker, CHUNK_SIZE, MARKER_SUFFIX};
+use common::TestDir;
+
+/// Set for the child process that gets killed in the middle of its copy
+const CHILD_ROOT: &str = "ROBOCOPYRS_RESTARTABLE_CHILD_ROOT";
+
+fn restartable<'a>(source: &'a Path, destination: &'a Path, bandwidth: Option<u64>) -> RobocopyCommand<'a> {
+    RobocopyCommand {
+        source,
+        destination,
+        copy_mode: Some(CopyMode::RESTARTABLE_MODE),
+        performance_options: bandwidth.map(|bytes_per_sec| PerformanceOptions::PerformanceChoiceOnly(PerformanceChoice::Bandwidth(bytes_per_sec))),
+        ..RobocopyCommand::default()
+    }
+}
+
+#[test]
+fn resumes_a_killed_copy() {
+    if let Some(root) = env::var_os(CHILD_ROOT) {
+        let root = PathBuf::from(root);
+        restartable(&root.join("source"), &root.join("destination"), Some(CHUNK_SIZE)).execute_native();
+        return;
+    }
+
+    let dir = TestDir::new("restartable");
+    let (source, destination) = (&dir.source, &dir.destination);
+
+    let contents: Vec<u8> = (0..6 * CHUNK_SIZE).map(|i| (i % 251) as u8).collect();
+    fs::write(source.join("large.bin"), &contents).unwrap();
+    let copied_file = destination.join("large.bin");
+
+    let mut child = Command::new(env::current_exe().unwrap())
+        .args(["--exact", "resumes_a_killed_copy", "--test-threads=1"])
+        .env(CHILD_ROOT, &dir.root)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(30);
+    while RestartMarker::read(&copied_file).ok().flatten().is_none_or(|marker| marker.offset < CHUNK_SIZE) {
+        assert!(Instant::now() < deadline, "the copy never recorded its progress");
+        thread::sleep(Duration::from_millis(10));
+    }
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    let interrupted = RestartMarker::read(&copied_file).unwrap().unwrap();
+    assert!(interrupted.offset < contents.len() as u64, "the copy finished before it was killed");
+
+    let report = restartable(source, destination, None).execute_native();
+    assert_eq!(report.exit_code_bits(), 1);
+    assert_eq!(report.bytes_transferred, contents.len() as u64 - interrupted.offset);
+    assert_eq!(fs::read(&copied_file).unwrap(), contents);
+    assert_eq!(RestartMarker::read(&copied_file).unwrap(), None);
+}
+
+#[test]
+fn purge_removes_markers_whose_file_is_gone() {
+    let dir = TestDir::new("orphaned-markers");
+    let (source, destination) = (&dir.source, &dir.destination);
+    fs::create_dir_all(destination).unwrap();
+    for name in ["extra.bin", "gone.bin"] {
+        fs::write(destination.join(name), name).unwrap();
+        RestartMarker::new(2 * CHUNK_SIZE, SystemTime::now(), CHUNK_SIZE).write(&destination.join(name)).unwrap();
+    }
+    fs::remove_file(destination.join("gone.bin")).unwrap();
+
+    let command = RobocopyCommand { source, destination, ..RobocopyCommand::default() };
+    let plan = command.plan().unwrap();
+    let extras: Vec<&Path> = plan.entries.iter().filter(|entry| entry.class == FileClass::Extra).map(|entry| entry.path.as_path()).collect();
+    let orphan = format!("gone.bin{}", MARKER_SUFFIX);
+    assert_eq!(extras, vec![Path::new("extra.bin"), Path::new(&orphan)]);
+
+    let purge = RobocopyCommand { remove_files_and_dirs_not_in_src: true, ..command };
+    let report = purge.execute_native();
+    assert_eq!((report.exit_code_bits(), report.summary.files.extras), (2, 2));
+    assert_eq!(fs::read_dir(destination).unwrap().count(), 0);
+}
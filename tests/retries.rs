@@ -0,0 +1,196 @@
+//! Copies in native execution that fail, and are retried if the error is transient.
+//! Most of them fail because the file size limit of the process is too low.
+//! The limit applies to the whole process, so these tests have a binary of their own.
+
+#![cfg(unix)]
+
+mod common;
+
+use std::{fs, os::unix::fs::MetadataExt, path::Path, process::Command, sync::{Mutex, MutexGuard}, time::{Duration, Instant}};
+
+use robocopyrs::{log, CopyMode, Move, RobocopyCommand};
+use robocopyrs::filter::{FileExclusionFilter, Filter};
+use robocopyrs::native::Outcome;
+use robocopyrs::performance::RetrySettings;
+use robocopyrs::restart::{self, RestartMarker, CHUNK_SIZE};
+use common::TestDir;
+
+/// Held by the test that writes its fixtures or runs with the limit
+static ONE_AT_A_TIME: Mutex<()> = Mutex::new(());
+
+/// The file size limit of the process lowered until it is dropped.
+/// Writing past it fails with EFBIG instead of raising SIGXFSZ.
+struct FileSizeLimit<'l> {
+    _one_at_a_time: MutexGuard<'l, ()>,
+}
+
+impl<'l> FileSizeLimit<'l> {
+    fn set(guard: MutexGuard<'l, ()>, bytes: u64) -> Self {
+        set_file_size_limit(bytes);
+        Self { _one_at_a_time: guard }
+    }
+}
+
+impl Drop for FileSizeLimit<'_> {
+    fn drop(&mut self) {
+        set_file_size_limit(libc::RLIM_INFINITY);
+    }
+}
+
+fn set_file_size_limit(bytes: libc::rlim_t) {
+    unsafe {
+        libc::signal(libc::SIGXFSZ, libc::SIG_IGN);
+        assert_eq!(libc::setrlimit(libc::RLIMIT_FSIZE, &libc::rlimit { rlim_cur: bytes, rlim_max: libc::RLIM_INFINITY }), 0);
+    }
+}
+
+fn one_at_a_time() -> MutexGuard<'static, ()> {
+    ONE_AT_A_TIME.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+fn no_retries() -> Option<RetrySettings> {
+    Some(RetrySettings {
+        specify_retries_failed_copies: Some(3),
+        specify_wait_between_retries: Some(0),
+        save_specifications: false,
+        await_share_names_def: false,
+    })
+}
+
+#[test]
+fn permanent_errors_fail_without_retrying() {
+    let guard = one_at_a_time();
+    let dir = TestDir::new("retries");
+    let (source, destination) = (&dir.source, &dir.destination);
+    fs::write(source.join("large.bin"), vec![7; 64 * 1024]).unwrap();
+
+    let limit = FileSizeLimit::set(guard, 4096);
+
+    let command = RobocopyCommand {
+        source,
+        destination,
+        retry_settings: no_retries(),
+        ..RobocopyCommand::default()
+    };
+    let report = command.execute_native();
+    drop(limit);
+
+    let entry = report.failures().next().unwrap();
+    assert!(matches!(entry.outcome, Outcome::Failed(_)));
+    assert_eq!(entry.failed_attempts.len(), 1);
+    assert_eq!((report.exit_code_bits(), report.retries()), (8, 0));
+}
+
+#[test]
+fn busy_files_are_retried_after_the_wait() {
+    if !cfg!(target_os = "linux") {
+        eprintln!("skipped, only Linux keeps running executables from being written to");
+        return;
+    }
+
+    let _one_at_a_time = one_at_a_time();
+    let dir = TestDir::new("busy");
+    let (source, destination) = (&dir.source, &dir.destination);
+    fs::create_dir_all(destination).unwrap();
+    fs::write(source.join("tool"), b"new version").unwrap();
+    // a running executable cannot be opened for writing, that fails with ETXTBSY until it exits
+    fs::copy("/bin/sleep", destination.join("tool")).unwrap();
+    let mut running = Command::new(destination.join("tool")).arg("1.5").spawn().unwrap();
+
+    let command = RobocopyCommand {
+        source,
+        destination,
+        retry_settings: Some(RetrySettings {
+            specify_retries_failed_copies: Some(5),
+            specify_wait_between_retries: Some(1),
+            save_specifications: false,
+            await_share_names_def: false,
+        }),
+        ..RobocopyCommand::default()
+    };
+    let start = Instant::now();
+    let report = command.execute_native();
+    let elapsed = start.elapsed();
+    running.wait().unwrap();
+
+    let entry = report.entries.iter().find(|entry| !entry.entry.is_dir).unwrap();
+    let attempts = entry.failed_attempts.len();
+    assert_eq!(entry.outcome, Outcome::Done);
+    assert!((1..=5).contains(&attempts), "{:?}", entry.failed_attempts);
+    assert!(entry.failed_attempts.iter().all(|error| error.contains("os error 26")), "{:?}", entry.failed_attempts);
+    assert_eq!((report.exit_code_bits(), report.retries()), (1, attempts));
+    assert!(elapsed >= Duration::from_secs(attempts as u64));
+    assert_eq!(fs::read(destination.join("tool")).unwrap(), b"new version");
+
+    let text = log::text(&command, &report);
+    assert_eq!(text.matches("ERROR 26 (0x0000001A)").count(), attempts, "{}", text);
+    assert_eq!(text.matches("Waiting 1 seconds... Retrying...").count(), attempts, "{}", text);
+}
+
+#[test]
+fn moves_that_fail_to_copy_keep_their_source() {
+    // the move only copies when the source is on another file system than the destination
+    let shm = Path::new("/dev/shm");
+    let temp = std::env::temp_dir();
+    if !shm.is_dir() || fs::metadata(shm).unwrap().dev() == fs::metadata(&temp).unwrap().dev() {
+        eprintln!("skipped, there is no second file system to move from");
+        return;
+    }
+
+    let guard = one_at_a_time();
+    let (from, to) = (TestDir::in_dir(shm, "failed-move"), TestDir::new("failed-move"));
+    let (source, destination) = (&from.source, &to.destination);
+    fs::write(source.join("large.bin"), vec![7; 64 * 1024]).unwrap();
+    fs::write(source.join("small.txt"), b"small").unwrap();
+    let limit = FileSizeLimit::set(guard, 4096);
+
+    let command = RobocopyCommand {
+        source,
+        destination,
+        mv: Some(Move::FILES),
+        retry_settings: no_retries(),
+        ..RobocopyCommand::default()
+    };
+    let report = command.execute_native();
+    drop(limit);
+
+    assert_eq!(report.exit_code_bits(), 9);
+    let failures: Vec<&Path> = report.failures().map(|entry| entry.entry.path.as_path()).collect();
+    assert_eq!(failures, vec![Path::new("large.bin")]);
+    assert_eq!(fs::read(source.join("large.bin")).unwrap(), vec![7; 64 * 1024]);
+    assert!(!source.join("small.txt").exists());
+    assert_eq!(fs::read(destination.join("small.txt")).unwrap(), b"small");
+}
+
+#[test]
+fn partial_restartable_copies_are_not_skipped_as_older() {
+    let guard = one_at_a_time();
+    let dir = TestDir::new("partial");
+    let (source, destination) = (&dir.source, &dir.destination);
+    let contents: Vec<u8> = (0..5 * CHUNK_SIZE / 2).map(|i| (i % 251) as u8).collect();
+    fs::write(source.join("large.bin"), &contents).unwrap();
+    let copied_file = destination.join("large.bin");
+
+    let command = RobocopyCommand {
+        source,
+        destination,
+        copy_mode: Some(CopyMode::RESTARTABLE_MODE),
+        filter: Some(Filter { file_exclusion_filter: Some(FileExclusionFilter::OLDER), ..Filter::default() }),
+        retry_settings: no_retries(),
+        ..RobocopyCommand::default()
+    };
+    let limit = FileSizeLimit::set(guard, 3 * CHUNK_SIZE / 2);
+    let report = command.execute_native();
+    drop(limit);
+
+    assert_eq!(report.exit_code_bits(), 8);
+    assert_eq!(RestartMarker::read(&copied_file).unwrap().map(|marker| marker.offset), Some(CHUNK_SIZE));
+    assert_eq!(fs::metadata(&copied_file).unwrap().modified().unwrap(), restart::partial_file_time());
+
+    let report = command.execute_native();
+    assert_eq!(report.exit_code_bits(), 1);
+    assert_eq!(report.bytes_transferred, contents.len() as u64 - CHUNK_SIZE);
+    assert_eq!(fs::read(&copied_file).unwrap(), contents);
+    assert_eq!(fs::metadata(&copied_file).unwrap().modified().unwrap(), fs::metadata(source.join("large.bin")).unwrap().modified().unwrap());
+    assert_eq!(RestartMarker::read(&copied_file).unwrap(), None);
+}
@@ -0,0 +1,138 @@
+//! Security information of `/copy:S` and `/copy:U` in native execution
+
+#![cfg(unix)]
+
+mod common;
+
+use std::{env, fs, os::unix::fs::PermissionsExt, path::PathBuf};
+
+use robocopyrs::{FileProperties, RobocopyCommand};
+use robocopyrs::security::ACL_ACCESS_XATTR;
+use common::TestDir;
+
+/// Set to a directory on a file system without user extended attributes, such as a ramfs mount
+const NO_XATTR_DIR: &str = "ROBOCOPYRS_NO_XATTR_DIR";
+
+/// `/copy:DATS`, or `/copy:DATSU` with auditing
+fn copy_security(auditing: bool) -> FileProperties {
+    FileProperties::_MULTIPLE([true, true, true, true, false, auditing])
+}
+
+/// A POSIX access ACL in the extended attribute format, granting user 4242 read access
+fn acl() -> Vec<u8> {
+    let mut value = 2u32.to_le_bytes().to_vec();
+    for (tag, perm, id) in [(0x01u16, 6u16, u32::MAX), (0x02, 4, 4242), (0x04, 4, u32::MAX), (0x10, 4, u32::MAX), (0x20, 0, u32::MAX)] {
+        value.extend_from_slice(&tag.to_le_bytes());
+        value.extend_from_slice(&perm.to_le_bytes());
+        value.extend_from_slice(&id.to_le_bytes());
+    }
+    value
+}
+
+#[test]
+fn s_copies_acls_and_user_attributes_and_removes_extra_ones() {
+    let dir = TestDir::new("security");
+    let (source, destination) = (&dir.source, &dir.destination);
+    fs::create_dir_all(destination).unwrap();
+    fs::write(source.join("a.txt"), b"a").unwrap();
+    fs::write(destination.join("a.txt"), b"old").unwrap();
+    if xattr::set(source.join("a.txt"), "user.comment", b"source").is_err() {
+        eprintln!("skipped, the file system has no user extended attributes");
+        return;
+    }
+    xattr::set(destination.join("a.txt"), "user.stale", b"destination").unwrap();
+    let acl = xattr::set(source.join("a.txt"), ACL_ACCESS_XATTR, &acl()).is_ok();
+    let auditing = xattr::set(source.join("a.txt"), "security.robocopyrs", b"audit").is_ok();
+
+    let command = RobocopyCommand {
+        source,
+        destination,
+        copy_file_properties: Some(copy_security(auditing)),
+        ..RobocopyCommand::default()
+    };
+    let report = command.execute_native();
+    assert_eq!(report.exit_code_bits(), 1);
+    assert_eq!(report.failures().count(), 0);
+    assert_eq!(report.degraded, vec![]);
+
+    let copy = destination.join("a.txt");
+    assert_eq!(fs::read(&copy).unwrap(), b"a");
+    assert_eq!(xattr::get(&copy, "user.comment").unwrap(), Some(b"source".to_vec()));
+    assert_eq!(xattr::get(&copy, "user.stale").unwrap(), None);
+    if acl {
+        assert_eq!(xattr::get(&copy, ACL_ACCESS_XATTR).unwrap(), xattr::get(source.join("a.txt"), ACL_ACCESS_XATTR).unwrap());
+    }
+    if auditing {
+        assert_eq!(xattr::get(&copy, "security.robocopyrs").unwrap(), Some(b"audit".to_vec()));
+    }
+
+    fs::write(source.join("b.txt"), b"b").unwrap();
+    xattr::set(source.join("b.txt"), "user.comment", b"source").unwrap();
+    let plain = RobocopyCommand { copy_file_properties: None, ..command.clone() };
+    assert_eq!(plain.execute_native().exit_code_bits(), 1);
+    assert_eq!(xattr::get(destination.join("b.txt"), "user.comment").unwrap(), None);
+}
+
+#[test]
+fn s_copies_to_files_and_directories_that_end_up_read_only() {
+    if !common::unprivileged("s_copies_to_files_and_directories_that_end_up_read_only") {
+        return;
+    }
+
+    let dir = TestDir::new("security-read-only");
+    let (source, destination) = (&dir.source, &dir.destination);
+    fs::create_dir_all(source.join("locked")).unwrap();
+    fs::write(source.join("locked/a.txt"), b"a").unwrap();
+    if xattr::set(source.join("locked/a.txt"), "user.foo", b"file").is_err() {
+        eprintln!("skipped, the file system has no user extended attributes");
+        return;
+    }
+    xattr::set(source.join("locked"), "user.foo", b"directory").unwrap();
+    fs::set_permissions(source.join("locked/a.txt"), fs::Permissions::from_mode(0o444)).unwrap();
+    fs::set_permissions(source.join("locked"), fs::Permissions::from_mode(0o555)).unwrap();
+
+    let command = RobocopyCommand {
+        source,
+        destination,
+        copy_file_properties: Some(copy_security(false)),
+        ..RobocopyCommand::default()
+    };
+    let report = command.execute_native();
+    assert_eq!(report.failures().count(), 0, "{:?}", report.failures().collect::<Vec<_>>());
+    assert_eq!((report.exit_code_bits(), report.degraded.len()), (1, 0));
+
+    for (path, value, mode) in [("locked/a.txt", b"file".as_slice(), 0o444), ("locked", b"directory".as_slice(), 0o555)] {
+        let copy = destination.join(path);
+        assert_eq!(xattr::get(&copy, "user.foo").unwrap().as_deref(), Some(value));
+        assert_eq!(fs::metadata(&copy).unwrap().permissions().mode() & 0o777, mode);
+    }
+}
+
+#[test]
+fn s_degrades_on_destinations_without_extended_attributes() {
+    let no_xattr = env::var_os(NO_XATTR_DIR).map(PathBuf::from).unwrap_or_else(env::temp_dir);
+    let (from, to) = (TestDir::new("security-source"), TestDir::in_dir(&no_xattr, "security-no-xattr"));
+    let (source, destination) = (&from.source, &to.destination);
+    fs::write(source.join("a.txt"), b"a").unwrap();
+    if xattr::set(source.join("a.txt"), "user.comment", b"source").is_err() {
+        eprintln!("skipped, the file system has no user extended attributes");
+        return;
+    }
+    if xattr::set(&to.root, "user.robocopyrs.probe", b"").is_ok() {
+        eprintln!("skipped, the file system has user extended attributes, set {} to one without", NO_XATTR_DIR);
+        return;
+    }
+
+    let command = RobocopyCommand {
+        source,
+        destination,
+        copy_file_properties: Some(copy_security(false)),
+        ..RobocopyCommand::default()
+    };
+    let report = command.execute_native();
+    assert_eq!(report.exit_code_bits(), 1);
+    assert_eq!(report.failures().count(), 0);
+    let degraded: Vec<(PathBuf, &str)> = report.degraded.iter().map(|degradation| (degradation.path.clone(), degradation.name.as_str())).collect();
+    assert_eq!(degraded, vec![(destination.join("a.txt"), "user.comment")]);
+    assert_eq!(fs::read(destination.join("a.txt")).unwrap(), b"a");
+}
@@ -0,0 +1,40 @@
+//! Byte sizes of the `/max` and `/min` filters and the I/O options
+
+use robocopyrs::filter::Filter;
+use robocopyrs::size::ByteSize;
+
+#[test]
+fn suffixes_are_binary_unless_they_end_in_a_decimal_b() {
+    assert_eq!("512".parse(), Ok(ByteSize(512)));
+    assert_eq!("512b".parse(), Ok(ByteSize(512)));
+    assert_eq!("10MB".parse(), Ok(ByteSize(10_000_000)));
+    assert_eq!("10MiB".parse(), Ok(ByteSize(10 << 20)));
+    assert_eq!("10m".parse(), Ok(ByteSize(10 << 20)));
+    assert_eq!("1.5G".parse(), Ok(ByteSize(3 << 29)));
+    assert_eq!("1.5 GB".parse(), Ok(ByteSize(1_500_000_000)));
+    assert_eq!(" 2 kib ".parse(), Ok(ByteSize(2048)));
+}
+
+#[test]
+fn invalid_sizes_are_rejected() {
+    assert_eq!("18446744073709551615".parse(), Ok(ByteSize(u64::MAX)));
+    assert!("18446744073709551616".parse::<ByteSize>().is_err());
+    assert!("16777216T".parse::<ByteSize>().is_err());
+    assert!("16777216.5T".parse::<ByteSize>().is_err());
+    assert!("1.5".parse::<ByteSize>().is_err());
+    assert!("1.5b".parse::<ByteSize>().is_err());
+    assert_eq!("2.0".parse(), Ok(ByteSize(2)));
+    for invalid in ["10x", "10 kilobytes", "10PB", "", "k", "-1", "1.2.3k"] {
+        assert!(invalid.parse::<ByteSize>().is_err(), "{}", invalid);
+    }
+}
+
+#[test]
+fn filters_reject_a_minimum_above_the_maximum() {
+    let sizes = |min: u64, max: u64| Filter { min_size: Some(ByteSize(min)), max_size: Some(ByteSize(max)), ..Filter::default() };
+
+    assert_eq!(sizes(1024, 1024).validate(), Ok(()));
+    assert_eq!(sizes(1024, 4096).validate(), Ok(()));
+    assert!(sizes(4096, 1024).validate().is_err());
+    assert_eq!(Filter { min_size: Some(ByteSize(4096)), ..Filter::default() }.validate(), Ok(()));
+}
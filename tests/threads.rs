@@ -0,0 +1,54 @@
+//! Copying on several threads in native execution
+
+mod common;
+
+use std::{fs, path::PathBuf};
+
+use robocopyrs::RobocopyCommand;
+use robocopyrs::native::Outcome;
+use robocopyrs::performance::{PerformanceChoice, PerformanceOptions};
+use common::TestDir;
+
+#[test]
+fn threads_copy_every_file_and_report_in_plan_order() {
+    let dir = TestDir::new("threads");
+    let (source, destination) = (&dir.source, &dir.destination);
+    for dir in 0..8 {
+        fs::create_dir_all(source.join(format!("dir{}/nested", dir))).unwrap();
+        for file in 0..16 {
+            fs::write(source.join(format!("dir{}/file{}.bin", dir, file)), vec![file as u8; 1000 * file]).unwrap();
+        }
+        fs::write(source.join(format!("dir{}/nested/leaf.txt", dir)), format!("leaf {}", dir)).unwrap();
+    }
+    fs::create_dir_all(destination.join("extra")).unwrap();
+    fs::write(destination.join("extra/old.txt"), b"old").unwrap();
+
+    let command = RobocopyCommand {
+        source,
+        destination,
+        empty_dir_copy: true,
+        remove_files_and_dirs_not_in_src: true,
+        performance_options: Some(PerformanceOptions::PerformanceChoiceOnly(PerformanceChoice::Threads(8))),
+        ..RobocopyCommand::default()
+    };
+    let planned: Vec<PathBuf> = command.plan().unwrap().entries.into_iter().map(|entry| entry.path).collect();
+    let report = command.execute_native();
+    assert_eq!(report.exit_code_bits(), 3);
+    assert_eq!(report.failures().count(), 0);
+    assert_eq!(report.entries.iter().map(|entry| entry.entry.path.clone()).collect::<Vec<_>>(), planned);
+    assert_eq!((report.summary.files.copied, report.summary.files.extras, report.summary.dirs.extras), (8 * 17, 1, 1));
+    assert_eq!(report.summary.bytes.copied, 8 * (0..16).map(|file| 1000 * file).sum::<u64>() + 8 * 6);
+
+    for dir in 0..8 {
+        for file in 0..16 {
+            let name = format!("dir{}/file{}.bin", dir, file);
+            assert_eq!(fs::read(destination.join(&name)).unwrap(), fs::read(source.join(&name)).unwrap());
+        }
+        assert_eq!(fs::read_to_string(destination.join(format!("dir{}/nested/leaf.txt", dir))).unwrap(), format!("leaf {}", dir));
+    }
+    assert!(!destination.join("extra").exists());
+
+    let report = command.execute_native();
+    assert_eq!(report.exit_code_bits(), 0);
+    assert!(report.entries.iter().filter(|entry| !entry.entry.is_dir).all(|entry| entry.outcome == Outcome::Skipped));
+}
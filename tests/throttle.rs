@@ -0,0 +1,37 @@
+//! Bandwidth limits in native execution
+
+mod common;
+
+use std::{fs, time::{Duration, Instant}};
+
+use robocopyrs::RobocopyCommand;
+use robocopyrs::performance::{PerformanceChoice, PerformanceOptions};
+use common::TestDir;
+
+#[test]
+fn bandwidth_and_inter_packet_gap_slow_down_copying() {
+    let dir = TestDir::new("throttle");
+    let (source, destination) = (&dir.source, &dir.destination);
+    let contents: Vec<u8> = (0..5 * PerformanceChoice::INTER_PACKET_GAP_BLOCK).map(|byte| byte as u8).collect();
+    fs::write(source.join("file.bin"), &contents).unwrap();
+
+    // The first block passes at once and the other four take a second at 4 blocks per second
+    for choice in [PerformanceChoice::Bandwidth(4 * PerformanceChoice::INTER_PACKET_GAP_BLOCK), PerformanceChoice::InterPacketGap(250)] {
+        let _ = fs::remove_dir_all(destination);
+        let command = RobocopyCommand {
+            source,
+            destination,
+            performance_options: Some(PerformanceOptions::PerformanceChoiceOnly(choice)),
+            ..RobocopyCommand::default()
+        };
+
+        let start = Instant::now();
+        let report = command.execute_native();
+        let elapsed = start.elapsed();
+        assert_eq!(report.exit_code_bits(), 1);
+        assert_eq!(report.summary.bytes.copied, contents.len() as u64);
+        assert!(elapsed >= Duration::from_millis(900), "{:?} copied in {:?}", choice, elapsed);
+        assert!(elapsed < Duration::from_secs(10), "{:?} copied in {:?}", choice, elapsed);
+        assert_eq!(fs::read(destination.join("file.bin")).unwrap(), contents);
+    }
+}
@@ -0,0 +1,114 @@
+//! `/fft` and `/dst` tolerances when classifying files
+
+mod common;
+
+use std::{fs, path::Path, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use robocopyrs::{FilesystemOptions, RobocopyCommand};
+use robocopyrs::evaluate::{EntryMetadata, FileClass, TimeTolerance};
+use common::TestDir;
+
+const EXACT: TimeTolerance = TimeTolerance { fat_file_times: false, daylight_saving_time: false };
+const FAT: TimeTolerance = TimeTolerance { fat_file_times: true, daylight_saving_time: false };
+const DST: TimeTolerance = TimeTolerance { fat_file_times: false, daylight_saving_time: true };
+const FAT_AND_DST: TimeTolerance = TimeTolerance { fat_file_times: true, daylight_saving_time: true };
+
+fn base() -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+}
+
+/// Classifies a source modified `offset` after the destination, or before it if negative
+fn classify(offset: Duration, source_is_later: bool, tolerance: TimeTolerance) -> FileClass {
+    let source_modified = if source_is_later { base() + offset } else { base() - offset };
+    let source = EntryMetadata::file(10, source_modified);
+    let destination = EntryMetadata::file(10, base());
+
+    FileClass::of_with(Some(&source), Some(&destination), tolerance).unwrap()
+}
+
+const NANO: Duration = Duration::from_nanos(1);
+
+#[test]
+fn exact_times_have_no_tolerance() {
+    assert_eq!(classify(Duration::ZERO, true, EXACT), FileClass::Same);
+    assert_eq!(classify(NANO, true, EXACT), FileClass::Newer);
+    assert_eq!(classify(NANO, false, EXACT), FileClass::Older);
+}
+
+#[test]
+fn fat_times_are_the_same_up_to_two_seconds_apart() {
+    assert_eq!(classify(TimeTolerance::FAT_GRANULARITY - NANO, true, FAT), FileClass::Same);
+    assert_eq!(classify(TimeTolerance::FAT_GRANULARITY, true, FAT), FileClass::Same);
+    assert_eq!(classify(TimeTolerance::FAT_GRANULARITY, false, FAT), FileClass::Same);
+    assert_eq!(classify(TimeTolerance::FAT_GRANULARITY + NANO, true, FAT), FileClass::Newer);
+    assert_eq!(classify(TimeTolerance::FAT_GRANULARITY + NANO, false, FAT), FileClass::Older);
+}
+
+#[test]
+fn dst_times_are_the_same_exactly_an_hour_apart() {
+    assert_eq!(classify(TimeTolerance::DST_OFFSET, true, DST), FileClass::Same);
+    assert_eq!(classify(TimeTolerance::DST_OFFSET, false, DST), FileClass::Same);
+    assert_eq!(classify(TimeTolerance::DST_OFFSET - NANO, true, DST), FileClass::Newer);
+    assert_eq!(classify(TimeTolerance::DST_OFFSET + NANO, false, DST), FileClass::Older);
+    assert_eq!(classify(TimeTolerance::DST_OFFSET, true, EXACT), FileClass::Newer);
+}
+
+#[test]
+fn dst_with_fat_times_allows_two_seconds_around_the_hour() {
+    let granularity = TimeTolerance::FAT_GRANULARITY;
+
+    assert_eq!(classify(TimeTolerance::DST_OFFSET + granularity, true, FAT_AND_DST), FileClass::Same);
+    assert_eq!(classify(TimeTolerance::DST_OFFSET - granularity, false, FAT_AND_DST), FileClass::Same);
+    assert_eq!(classify(TimeTolerance::DST_OFFSET + granularity + NANO, true, FAT_AND_DST), FileClass::Newer);
+    assert_eq!(classify(TimeTolerance::DST_OFFSET - granularity - NANO, false, FAT_AND_DST), FileClass::Older);
+    assert_eq!(classify(granularity, true, FAT_AND_DST), FileClass::Same);
+}
+
+#[test]
+fn tolerated_times_still_compare_sizes() {
+    let source = EntryMetadata::file(10, base() + Duration::from_secs(1));
+    let destination = EntryMetadata::file(11, base());
+
+    assert_eq!(FileClass::of_with(Some(&source), Some(&destination), FAT), Some(FileClass::Changed));
+}
+
+#[test]
+fn tolerance_comes_from_the_filesystem_options() {
+    assert_eq!(TimeTolerance::from(&FilesystemOptions::ASSUME_FAT_FILE_TIMES), FAT);
+    assert_eq!(TimeTolerance::from(&FilesystemOptions::COMPENSATE_DST), DST);
+    assert_eq!(TimeTolerance::from(&FilesystemOptions::_MULTIPLE([false, true, false, true])), FAT_AND_DST);
+    assert_eq!(TimeTolerance::from(&FilesystemOptions::DISABLE_LONG_PATHS), EXACT);
+}
+
+#[test]
+fn planning_skips_files_within_the_tolerance() {
+    let dir = TestDir::new("time-tolerance");
+    let (source, destination) = (&dir.source, &dir.destination);
+    fs::create_dir_all(destination).unwrap();
+
+    let write = |dir: &Path, name: &str, modified: SystemTime| {
+        fs::write(dir.join(name), b"same").unwrap();
+        fs::File::options().write(true).open(dir.join(name)).unwrap().set_modified(modified).unwrap();
+    };
+    write(source, "fat.txt", base() + Duration::from_secs(2));
+    write(destination, "fat.txt", base());
+    write(source, "dst.txt", base() + Duration::from_secs(3600));
+    write(destination, "dst.txt", base());
+
+    let plan = |options| RobocopyCommand {
+        source,
+        destination,
+        filesystem_options: options,
+        ..RobocopyCommand::default()
+    }.plan().unwrap();
+    let class = |plan: &robocopyrs::plan::CopyPlan, name: &str| plan.entries.iter().find(|entry| entry.path == Path::new(name)).unwrap().class;
+
+    let exact = plan(None);
+    assert_eq!(class(&exact, "fat.txt"), FileClass::Newer);
+    assert_eq!(class(&exact, "dst.txt"), FileClass::Newer);
+
+    let tolerant = plan(Some(FilesystemOptions::_MULTIPLE([false, true, false, true])));
+    assert_eq!(class(&tolerant, "fat.txt"), FileClass::Same);
+    assert_eq!(class(&tolerant, "dst.txt"), FileClass::Same);
+    assert_eq!(tolerant.copies().count(), 0);
+}
@@ -0,0 +1,69 @@
+//! Timestamps of copied files and directories in native execution
+
+mod common;
+
+use std::{fs::{self, File}, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use robocopyrs::{DirectoryProperties, FileProperties, FilesystemOptions, RobocopyCommand};
+use robocopyrs::evaluate::FileClass;
+use robocopyrs::native::Outcome;
+use common::TestDir;
+
+fn base() -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(1_700_000_000) + Duration::from_nanos(123_456_789)
+}
+
+fn set_modified(path: &std::path::Path, modified: SystemTime) {
+    File::open(path).unwrap().set_modified(modified).unwrap();
+}
+
+#[test]
+fn timfix_fixes_files_copied_before() {
+    let dir = TestDir::new("timfix");
+    let (source, destination) = (&dir.source, &dir.destination);
+    fs::write(source.join("file.txt"), b"contents").unwrap();
+    set_modified(&source.join("file.txt"), base());
+
+    let command = RobocopyCommand {
+        source,
+        destination,
+        filesystem_options: Some(FilesystemOptions::ASSUME_FAT_FILE_TIMES),
+        fix_file_times: true,
+        ..RobocopyCommand::default()
+    };
+    assert_eq!(command.execute_native().exit_code_bits(), 1);
+    assert_eq!(fs::metadata(destination.join("file.txt")).unwrap().modified().unwrap(), base());
+
+    set_modified(&destination.join("file.txt"), base() + Duration::from_secs(1));
+    let report = command.execute_native();
+    let entry = report.entries.iter().find(|entry| !entry.entry.is_dir).unwrap();
+    assert_eq!((entry.entry.class, &entry.outcome), (FileClass::Same, &Outcome::Skipped));
+    assert_eq!(report.exit_code_bits(), 0);
+    assert_eq!(fs::metadata(destination.join("file.txt")).unwrap().modified().unwrap(), base());
+}
+
+#[test]
+fn directory_times_are_set_after_their_contents() {
+    let dir = TestDir::new("dir-times");
+    let (source, destination) = (&dir.source, &dir.destination);
+    fs::create_dir_all(source.join("outer/inner")).unwrap();
+    fs::write(source.join("outer/inner/file.txt"), b"contents").unwrap();
+    fs::write(source.join("outer/file.txt"), b"contents").unwrap();
+    set_modified(&source.join("outer/inner"), base());
+    set_modified(&source.join("outer"), base() + Duration::from_secs(60));
+
+    let command = RobocopyCommand {
+        source,
+        destination,
+        copy_file_properties: Some(FileProperties::_MULTIPLE([true, true, true, false, false, false])),
+        copy_dir_properties: Some(DirectoryProperties::all()),
+        ..RobocopyCommand::default()
+    };
+    let report = command.execute_native();
+    assert_eq!(report.exit_code_bits(), 1);
+    assert_eq!(report.failures().count(), 0);
+
+    let modified = |path: &str| fs::metadata(destination.join(path)).unwrap().modified().unwrap();
+    assert_eq!(modified("outer/inner"), base());
+    assert_eq!(modified("outer"), base() + Duration::from_secs(60));
+}
@@ -0,0 +1,51 @@
+//! Hash verification of copied files in native execution
+
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::{fs, os::unix::fs::symlink, path::Path};
+
+use robocopyrs::RobocopyCommand;
+use robocopyrs::native::Outcome;
+use robocopyrs::verify::{HashAlgorithm, Verification};
+use common::TestDir;
+
+/// Every read of this file returns a new UUID, so a copy of it never matches a second read
+const CHANGING_FILE: &str = "/proc/sys/kernel/random/uuid";
+
+#[test]
+fn copies_are_verified_and_mismatches_fail_after_a_recopy() {
+    let dir = TestDir::new("verify");
+    let (source, destination) = (&dir.source, &dir.destination);
+    fs::write(source.join("stable.txt"), b"stable").unwrap();
+    symlink(CHANGING_FILE, source.join("uuid")).unwrap();
+
+    let command = RobocopyCommand {
+        source,
+        destination,
+        verification: Some(Verification { algorithm: HashAlgorithm::Sha256, recopy: true }),
+        ..RobocopyCommand::default()
+    };
+    let report = command.execute_native();
+    assert_eq!(report.exit_code_bits(), 9);
+
+    let entry = |path: &str| report.entries.iter().find(|entry| entry.entry.path == Path::new(path)).unwrap();
+    let stable = entry("stable.txt");
+    assert_eq!(stable.outcome, Outcome::Done);
+    assert_eq!(stable.digest, Some(HashAlgorithm::Sha256.hash_file(&source.join("stable.txt")).unwrap()));
+    assert_eq!(stable.digest.as_ref().unwrap().0.len(), 32);
+
+    let uuid = entry("uuid");
+    match &uuid.outcome {
+        Outcome::HashMismatch(mismatch) => {
+            assert_eq!(mismatch.algorithm, HashAlgorithm::Sha256);
+            assert_eq!(mismatch.destination, HashAlgorithm::Sha256.hash_file(&destination.join("uuid")).unwrap());
+            assert_ne!(mismatch.source, mismatch.destination);
+        },
+        outcome => panic!("the changing file was {:?}", outcome),
+    }
+    assert_eq!((uuid.failed_attempts.len(), uuid.digest.as_ref()), (2, None));
+    assert_eq!(report.hash_mismatches().count(), 1);
+    assert_eq!((report.summary.files.copied, report.summary.files.failed), (1, 1));
+}
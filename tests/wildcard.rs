@@ -0,0 +1,66 @@
+//! Robocopy compatible wildcards
+
+use std::path::Path;
+
+use robocopyrs::wildcard::{Wildcard, WildcardSet};
+
+#[test]
+fn names_match_case_insensitively() {
+    assert!(Wildcard::new("*.TXT").matches_name("notes.txt"));
+    assert!(Wildcard::new("readme").matches_name("README"));
+    assert!(Wildcard::new("ÄRGER.*").matches_name("ärger.md"));
+    assert!(!Wildcard::new("*.txt").matches_name("notes.txt.bak"));
+}
+
+#[test]
+fn star_dot_star_matches_names_without_an_extension() {
+    assert!(Wildcard::new("*.*").matches_name("Makefile"));
+    assert!(Wildcard::new("*.*").matches_name(".profile"));
+    assert!(Wildcard::new("*.*").matches_name("archive.tar.gz"));
+    assert!(!Wildcard::new("*.").matches_name("Makefile"));
+    assert!(!Wildcard::new("a*.*").matches_name("Makefile"));
+}
+
+#[test]
+fn question_marks_match_exactly_one_character() {
+    let pattern = Wildcard::new("file?.txt");
+    assert!(pattern.matches_name("file1.txt"));
+    assert!(!pattern.matches_name("file.txt"));
+    assert!(!pattern.matches_name("file12.txt"));
+    assert!(Wildcard::new("??").matches_name("ab") && !Wildcard::new("??").matches_name("abc"));
+}
+
+#[test]
+fn stars_backtrack() {
+    let pattern = Wildcard::new("a*b*c");
+    assert!(pattern.matches_name("abc"));
+    assert!(pattern.matches_name("aXbYc"));
+    assert!(pattern.matches_name("abbbcbc"));
+    assert!(pattern.matches_name("acbc"));
+    assert!(!pattern.matches_name("acb"));
+    assert!(!pattern.matches_name("abcd"));
+    assert!(Wildcard::new("*").matches_name("") && Wildcard::new("**").matches_name("x"));
+    assert!(Wildcard::new("*ab").matches_name("aab"));
+}
+
+#[test]
+fn path_patterns_match_full_paths_and_name_patterns_the_last_component() {
+    let path = Wildcard::new("C:\\Data\\*\\cache");
+    assert!(path.is_path_pattern());
+    assert!(path.matches_path(Path::new("c:/data/project/Cache")));
+    assert!(path.matches_path(Path::new("C:\\Data\\a\\b\\cache\\")));
+    assert!(!path.matches_path(Path::new("D:/Data/project/cache")));
+    assert!(!path.matches_name("cache"));
+
+    let name = Wildcard::new("cache");
+    assert!(!name.is_path_pattern());
+    assert!(name.matches_path(Path::new("/home/user/CACHE")));
+    assert!(!name.matches_path(Path::new("/home/cache/user")));
+    assert_eq!(Wildcard::new("/srv/build/").as_str(), "/srv/build/");
+    assert!(Wildcard::new("/srv/build/").matches_path(Path::new("/srv/build")));
+
+    let set = WildcardSet::new(&["*.tmp", "/srv/*/logs"]);
+    assert_eq!(set.match_path(Path::new("/srv/app/logs")).map(Wildcard::as_str), Some("/srv/*/logs"));
+    assert_eq!(set.match_path(Path::new("/srv/app/x.TMP")).map(Wildcard::as_str), Some("*.tmp"));
+    assert_eq!(set.match_path(Path::new("/srv/app/x.txt")), None);
+}